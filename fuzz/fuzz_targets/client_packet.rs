@@ -0,0 +1,18 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use cu_rs::packets::client::ClientPacket;
+
+// The first two bytes are the packet id, the rest its body. Anything that
+// decodes must encode back into a packet decoding to the same value.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let id = u16::from_le_bytes([data[0], data[1]]);
+
+    if let Ok(packet) = ClientPacket::decode(id, &data[2..]) {
+        let encoded = packet.encode();
+        let again = ClientPacket::decode(id, &encoded[7..]).expect("re-encoded packet failed to decode");
+        assert_eq!(packet, again);
+    }
+});
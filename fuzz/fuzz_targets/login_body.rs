@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use cu_rs::events::login::LoginData;
+
+// The login body is read as a string, with invalid UTF-8 turning into an
+// empty one.
+fuzz_target!(|data: &[u8]| {
+    let body = String::from_utf8(data.to_vec()).unwrap_or_default();
+    let _ = LoginData::from_body(body);
+});
@@ -0,0 +1,28 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use cu_rs::packets::rw::Reader;
+
+// Splits a request body into packets the same way `handle_packets` does, then
+// runs every reader primitive over each packet body.
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Reader::new(data);
+
+    while !reader.empty() {
+        let body = match reader.read_headers()
+            .and_then(|(_, len)| reader.read_bytes(len as usize))
+        {
+            Ok(body) => body,
+            Err(_) => break,
+        };
+
+        let mut r = Reader::new(body);
+        let _ = r.read_string();
+        let _ = r.read_i32_l();
+        let _ = r.read_message();
+        let _ = r.read_uleb128();
+        let _ = r.read_int::<i64>();
+        let _ = r.read_int::<f32>();
+        let _ = r.read_to_end();
+        assert!(r.empty());
+    }
+});
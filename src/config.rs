@@ -5,9 +5,24 @@ use std::io::{Write, Read};
 
 const CONFIG_DIR: &str = "config.json";
 
+/// # Multi Account Action
+/// What to do when a login is found to share hardware with another account.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiAccountAction {
+    /// Only log the match and notify staff.
+    Log,
+    /// Restrict the newer account.
+    Restrict,
+    /// Refuse the login of the newer account.
+    Block,
+}
+
 /// # Config
-/// The class storing the kisumi.rs config values.
+/// The class storing the kisumi.rs config values. Missing values are filled
+/// in with their defaults, so older configs keep loading.
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     pub http_ip: String,
     pub server_name: String,
@@ -16,6 +31,41 @@ pub struct Config {
     pub sql_db: String,
     pub sql_user: String,
     pub sql_passwd: String,
+
+    /// The oldest client build date (`YYYYMMDD`) allowed to log in. 0 allows
+    /// any build.
+    pub min_client_build: u32,
+    /// The release streams (eg `stable`, `cuttingedge`) allowed to log in.
+    pub allowed_streams: Vec<String>,
+    /// Exact client versions (eg `b20210125.2cuttingedge`) refused at login.
+    pub blocked_versions: Vec<String>,
+
+    /// The action taken when a login shares hardware with another account.
+    pub multi_account_action: MultiAccountAction,
+
+    /// The path to the MaxMind GeoLite2 City database used for geolocation.
+    pub geoip_db: String,
+
+    /// The channels players are told to join as they log in. Ripple's
+    /// `bancho_channels` has no column for this.
+    pub auto_join_channels: Vec<String>,
+
+    /// The number of messages a player may send within `spam_window` seconds
+    /// before being silenced automatically. 0 disables spam detection.
+    pub spam_message_limit: usize,
+    pub spam_window: u64,
+    /// The lengths in seconds of the automatic silences, escalating with each
+    /// repeat offence in a session. The last one is used for any further
+    /// offences.
+    pub spam_silence_durations: Vec<i32>,
+
+    /// The path to the JSON file holding the chat word filter rules. The
+    /// filter is disabled if the file does not exist.
+    pub filter_file: String,
+
+    /// The number of seconds a player may go without making a request before
+    /// they are logged out. 0 never times players out.
+    pub idle_timeout: u64,
 }
 
 impl Config {
@@ -30,6 +80,25 @@ impl Config {
             sql_db: "rosu".to_string(),
             sql_user: "rosu".to_string(),
             sql_passwd: "".to_string(),
+            min_client_build: 0,
+            allowed_streams: vec![
+                "stable".to_string(),
+                "beta".to_string(),
+                "cuttingedge".to_string(),
+                "tourney".to_string(),
+            ],
+            blocked_versions: vec![],
+            multi_account_action: MultiAccountAction::Log,
+            geoip_db: "GeoLite2-City.mmdb".to_string(),
+            auto_join_channels: vec![
+                "#osu".to_string(),
+                "#announce".to_string(),
+            ],
+            spam_message_limit: 10,
+            spam_window: 10,
+            spam_silence_durations: vec![300, 1800, 3600, 86400],
+            filter_file: "filters.json".to_string(),
+            idle_timeout: 120,
         }
     }
 
@@ -46,7 +115,7 @@ impl Config {
     /// Writes the current config to the file `crate::CONFIG_NAME`.
     /// ## Args
     /// - file_exists (`bool`): Whether the empty file already exists for the
-    ///     config. If set to false, a new file is created.
+    ///   config. If set to false, a new file is created.
     pub fn write_file(&self, file_exists: bool) {
         let mut f = if !file_exists {
             File::create(CONFIG_DIR).unwrap()
@@ -54,7 +123,7 @@ impl Config {
             File::open(CONFIG_DIR).unwrap()
         };
 
-        f.write(self.write_string().as_bytes()).expect("Could not write config file..."); // Sucks to suck if err.
+        f.write_all(self.write_string().as_bytes()).expect("Could not write config file..."); // Sucks to suck if err.
     }
 
     /// # Config Load
@@ -75,6 +144,12 @@ impl Config {
     }
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Ensures the presence of a config. If it doesnt exist, creates a new one and
 /// closes the program.
 pub fn ensure_config() -> Config {
@@ -84,6 +159,5 @@ pub fn ensure_config() -> Config {
         panic!("New config written to file!");
     }
 
-    let cfg = Config::from_file();
-    cfg
+    Config::from_file()
 }
@@ -1,3 +1,4 @@
 pub mod privileges;
 pub mod modes;
 pub mod packet_ids;
+pub mod countries;
@@ -1,10 +1,23 @@
 /// Enumeration representing custom implemented server-side modes.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum CustomMode {
     VANILLA,
     RELAX,
     AUTOPILOT,
 }
 
+impl CustomMode {
+    /// Returns the Ripple table holding the stats for the mode.
+    pub fn stats_table(&self) -> &'static str {
+        match self {
+            Self::VANILLA => "users_stats",
+            Self::RELAX => "rx_stats",
+            Self::AUTOPILOT => "ap_stats",
+        }
+    }
+}
+
 impl From<u8> for CustomMode {
     fn from(i: u8) -> Self {
         match i {
@@ -16,6 +29,8 @@ impl From<u8> for CustomMode {
 }
 
 /// Enumeration representing in-game modes.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum Mode {
     STANDARD,
     TAIKO,
@@ -23,6 +38,19 @@ pub enum Mode {
     MANIA,
 }
 
+impl Mode {
+    /// Returns the suffix used for the mode's columns in the Ripple database
+    /// (eg `pp_std`).
+    pub fn db_suffix(&self) -> &'static str {
+        match self {
+            Self::STANDARD => "std",
+            Self::TAIKO => "taiko",
+            Self::CATCH => "ctb",
+            Self::MANIA => "mania",
+        }
+    }
+}
+
 impl From<u8> for Mode {
     fn from(i: u8) -> Self {
         match i {
@@ -8,6 +8,7 @@ macro_rules! privilege_check {
     };
 }
 
+#[derive(Clone, Copy)]
 pub struct Privileges {
     pub privs: u32
 }
@@ -16,7 +17,7 @@ impl Privileges {
     // https://github.com/RealistikOsu/common/blob/master/constants/privileges.py
     // TODO: Maybe also make these part of the macro.
     const USER_PUBLIC               : u32 = 1;
-    const USER_NORMAL               : u32 = 2 << 0;
+    const USER_NORMAL               : u32 = 2;
     const USER_DONOR                : u32 = 2 << 1;
     const ADMIN_ACCESS_RAP          : u32 = 2 << 2;
     const ADMIN_MANAGE_USERS        : u32 = 2 << 3;
@@ -26,17 +27,24 @@ impl Privileges {
     const ADMIN_MANAGE_BEATMAPS     : u32 = 2 << 7;
     const ADMIN_MANAGE_SERVERS      : u32 = 2 << 8;
     const ADMIN_MANAGE_SETTINGS     : u32 = 2 << 9;
+    #[allow(dead_code)]
     const ADMIN_MANAGE_BETAKEYS     : u32 = 2 << 10;
+    #[allow(dead_code)]
     const ADMIN_MANAGE_REPORTS      : u32 = 2 << 11;
+    #[allow(dead_code)]
     const ADMIN_MANAGE_DOCS         : u32 = 2 << 12;
+    #[allow(dead_code)]
     const ADMIN_MANAGE_BADGES       : u32 = 2 << 13;
+    #[allow(dead_code)]
     const ADMIN_VIEW_RAP_LOGS       : u32 = 2 << 14;
     const ADMIN_MANAGE_PRIVILEGES   : u32 = 2 << 15;
     const ADMIN_SEND_ALERTS         : u32 = 2 << 16;
     const ADMIN_CHAT_MOD            : u32 = 2 << 17;
     const ADMIN_KICK_USERS          : u32 = 2 << 18;
     const USER_PENDING_VERIFICATION : u32 = 2 << 19;
+    #[allow(dead_code)]
     const USER_TOURNAMENT_STAFF     : u32 = 2 << 20;
+    #[allow(dead_code)]
     const ADMIN_CAKER               : u32 = 20 << 21;
 
     /// # Empty Privileges
@@ -54,7 +62,7 @@ impl Privileges {
     #[inline(always)]
     pub fn from_bitwise(privs: u32) -> Self {
         Self {
-            privs: privs
+            privs
         }
     }
 
@@ -76,6 +84,31 @@ impl Privileges {
     privilege_check!(kick_user, ADMIN_KICK_USERS);
     privilege_check!(unverified, USER_PENDING_VERIFICATION);
 
+    /// # Banned
+    /// Checks if the user is banned (neither public nor normal).
+    pub fn banned(&self) -> bool {
+        !self.public() && !self.normal()
+    }
+
+    /// # Locked
+    /// Checks if the user's account is locked (public but not normal).
+    pub fn locked(&self) -> bool {
+        self.public() && !self.normal()
+    }
+
+    /// # Restrict
+    /// Removes the public privilege, restricting the user.
+    pub fn restrict(&mut self) {
+        self.privs &= !Self::USER_PUBLIC;
+    }
+
+    /// # Verify
+    /// Clears the pending verification privilege, leaving the user public and
+    /// normal.
+    pub fn verify(&mut self) {
+        self.privs = (self.privs & !Self::USER_PENDING_VERIFICATION) | Self::USER_PUBLIC | Self::USER_NORMAL;
+    }
+
     /// # Has Any
     /// Checks if the privilege features any of the given bitwise flags.
     pub fn has_any(&self, flag: u32) -> bool {
@@ -106,10 +139,17 @@ impl Privileges {
     }
 }
 
+impl Default for Privileges {
+    fn default() -> Self { Self::new() }
+}
+
 // Special "Bancho Priv"
-const BP_PLAYER: u8 = 1 << 0 ;
+const BP_PLAYER: u8 = 1;
 const BP_BAT: u8 = 1 << 1;
 const BP_SUPPORTER: u8 = 1 << 2;
+#[allow(dead_code)]
 const BP_PEPPY: u8 = 1 << 3;
+#[allow(dead_code)]
 const BP_DEV: u8 = 1 << 4;
+#[allow(dead_code)]
 const BP_TOURNEY: u8 = 1 << 5;
@@ -0,0 +1,23 @@
+// Queries on the Ripple `bancho_channels` table.
+use crate::db::Database;
+use sqlx::FromRow;
+
+/// A `bancho_channels` row.
+#[derive(FromRow)]
+pub struct ChannelRow {
+    pub name: String,
+    pub description: String,
+    pub public_read: i8,
+    pub public_write: i8,
+    pub hidden: i8,
+}
+
+/// # Fetch Channels
+/// Fetches every chat channel stored in the database.
+pub async fn fetch_all(db: &Database) -> Result<Vec<ChannelRow>, sqlx::Error> {
+    sqlx::query_as::<_, ChannelRow>(
+        "SELECT name, description, public_read, public_write, hidden FROM bancho_channels"
+    )
+        .fetch_all(&db.pool)
+        .await
+}
@@ -0,0 +1,69 @@
+// Queries on the Ripple `hw_user` table.
+use crate::db::Database;
+use sqlx::FromRow;
+
+/// An account found to share hardware with another.
+#[derive(FromRow)]
+pub struct HardwareMatch {
+    pub id: i32,
+    pub username: String,
+}
+
+/// # Log Hardware
+/// Records a login from the given hardware, bumping the occurrence count if
+/// this user has logged in from it before.
+pub async fn log_login(db: &Database, user_id: i32, mac: &str, unique_id: &str, disk_id: &str) -> Result<(), sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE hw_user SET occurencies = occurencies + 1 \
+        WHERE userid = ? AND mac = ? AND unique_id = ? AND disk_id = ?"
+    )
+        .bind(user_id)
+        .bind(mac)
+        .bind(unique_id)
+        .bind(disk_id)
+        .execute(&db.pool)
+        .await?;
+
+    if res.rows_affected() == 0 {
+        sqlx::query(
+            "INSERT INTO hw_user (userid, mac, unique_id, disk_id, occurencies) VALUES (?, ?, ?, ?, 1)"
+        )
+            .bind(user_id)
+            .bind(mac)
+            .bind(unique_id)
+            .bind(disk_id)
+            .execute(&db.pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// # Fetch Hardware Matches
+/// Fetches all other accounts that have logged in with both the same
+/// uninstaller and disk serial hash.
+pub async fn fetch_matches(db: &Database, user_id: i32, unique_id: &str, disk_id: &str) -> Result<Vec<HardwareMatch>, sqlx::Error> {
+    sqlx::query_as::<_, HardwareMatch>(
+        "SELECT DISTINCT u.id, u.username FROM hw_user h INNER JOIN users u ON u.id = h.userid \
+        WHERE h.userid != ? AND h.unique_id = ? AND h.disk_id = ?"
+    )
+        .bind(user_id)
+        .bind(unique_id)
+        .bind(disk_id)
+        .fetch_all(&db.pool)
+        .await
+}
+
+/// # Fetch Unique Id Matches
+/// Fetches all other accounts that have logged in with the same uninstaller
+/// hash. Used for wine clients, whose other hashes are the same for everyone.
+pub async fn fetch_unique_id_matches(db: &Database, user_id: i32, unique_id: &str) -> Result<Vec<HardwareMatch>, sqlx::Error> {
+    sqlx::query_as::<_, HardwareMatch>(
+        "SELECT DISTINCT u.id, u.username FROM hw_user h INNER JOIN users u ON u.id = h.userid \
+        WHERE h.userid != ? AND h.unique_id = ?"
+    )
+        .bind(user_id)
+        .bind(unique_id)
+        .fetch_all(&db.pool)
+        .await
+}
@@ -0,0 +1,16 @@
+// Queries on the Ripple `rap_logs` table.
+use crate::db::Database;
+
+/// # RAP Log
+/// Adds an entry to the admin panel logs, attributed to `user_id`.
+pub async fn rap_log(db: &Database, user_id: i32, text: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO rap_logs (userid, text, datetime, through) VALUES (?, ?, UNIX_TIMESTAMP(), 'cu.rs')"
+    )
+        .bind(user_id)
+        .bind(text)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+pub mod users;
+pub mod hardware;
+pub mod logs;
+pub mod channels;
+
+use crate::config::Config;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions};
+
+const MAX_CONNECTIONS: u32 = 10;
+
+/// # Database
+/// A wrapper around the MySQL connection pool used to talk to the Ripple
+/// database.
+pub struct Database {
+    pub pool: MySqlPool,
+}
+
+impl Database {
+    /// # Database Connect
+    /// Creates a connection pool to the MySQL server specified by the `sql_*`
+    /// fields of the config.
+    pub async fn connect(conf: &Config) -> Result<Self, sqlx::Error> {
+        let opts = MySqlConnectOptions::new()
+            .host(&conf.sql_server)
+            .database(&conf.sql_db)
+            .username(&conf.sql_user)
+            .password(&conf.sql_passwd);
+
+        let pool = MySqlPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_with(opts)
+            .await?;
+
+        Ok(Self { pool })
+    }
+}
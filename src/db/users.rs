@@ -0,0 +1,172 @@
+// Queries on the Ripple `users` and related tables.
+use crate::db::Database;
+use crate::consts::modes::{Mode, CustomMode};
+use crate::objects::player::Stats;
+use sqlx::FromRow;
+
+/// The subset of a `users` row required to log a user in.
+#[derive(FromRow)]
+pub struct UserRow {
+    pub id: i32,
+    pub username: String,
+    pub username_safe: String,
+    pub password_md5: String,
+    pub privileges: i64,
+    pub silence_end: i32,
+}
+
+/// # Fetch User
+/// Fetches a user by their safe username, returning `None` if no such user
+/// exists.
+pub async fn fetch_by_safe_name(db: &Database, safe_name: &str) -> Result<Option<UserRow>, sqlx::Error> {
+    sqlx::query_as::<_, UserRow>(
+        "SELECT id, username, username_safe, password_md5, privileges, silence_end FROM users \
+        WHERE username_safe = ? LIMIT 1"
+    )
+        .bind(safe_name)
+        .fetch_optional(&db.pool)
+        .await
+}
+
+#[derive(FromRow)]
+struct StatsRow {
+    ranked_score: i64,
+    total_score: i64,
+    playcount: i32,
+    accuracy: f32,
+    pp: i32,
+}
+
+/// # Fetch Stats
+/// Fetches the user's stats for the given mode, including their global rank.
+/// Returns default stats if the user has no stats row.
+pub async fn fetch_stats(db: &Database, user_id: i32, mode: &Mode, c_mode: &CustomMode) -> Result<Stats, sqlx::Error> {
+    let suffix = mode.db_suffix();
+    let table = c_mode.stats_table();
+    let row = sqlx::query_as::<_, StatsRow>(&format!(
+        "SELECT ranked_score_{0} AS ranked_score, total_score_{0} AS total_score, \
+        playcount_{0} AS playcount, avg_accuracy_{0} AS accuracy, pp_{0} AS pp \
+        FROM {1} WHERE id = ? LIMIT 1", suffix, table
+    ))
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+    let row = match row {
+        Some(r) => r,
+        None => return Ok(Stats::new()),
+    };
+
+    // Rank is the amount of unrestricted players with more pp than us.
+    let (above,): (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM {1} s INNER JOIN users u ON u.id = s.id \
+        WHERE s.pp_{0} > ? AND u.privileges & 1", suffix, table
+    ))
+        .bind(row.pp)
+        .fetch_one(&db.pool)
+        .await?;
+
+    Ok(Stats {
+        ranked_score: row.ranked_score,
+        total_score: row.total_score,
+        accuracy: row.accuracy,
+        playcount: row.playcount,
+        rank: above as i32 + 1,
+        pp: row.pp,
+    })
+}
+
+/// # Set Country
+/// Sets the user's country in `users_stats`, if it has not been set before.
+pub async fn set_initial_country(db: &Database, user_id: i32, iso_code: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users_stats SET country = ? WHERE id = ? AND country = 'XX'")
+        .bind(iso_code)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Restrict User
+/// Removes the public privilege from the user, restricting them.
+pub async fn restrict(db: &Database, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET privileges = privileges & ~1, ban_datetime = UNIX_TIMESTAMP() WHERE id = ?")
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Set Privileges
+/// Sets the user's privileges to the given bitwise flags.
+pub async fn set_privileges(db: &Database, user_id: i32, privileges: u32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET privileges = ? WHERE id = ?")
+        .bind(privileges)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Set Silence
+/// Sets when the user's silence ends and why they were silenced. An end in
+/// the past (such as 0) lifts the silence.
+pub async fn set_silence(db: &Database, user_id: i32, silence_end: i64, reason: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET silence_end = ?, silence_reason = ? WHERE id = ?")
+        .bind(silence_end)
+        .bind(reason)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Update Latest Activity
+/// Marks the user as last active now.
+pub async fn update_latest_activity(db: &Database, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET latest_activity = UNIX_TIMESTAMP() WHERE id = ?")
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Add Friend
+/// Adds `friend_id` to the user's friends.
+pub async fn add_friend(db: &Database, user_id: i32, friend_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO users_relationships (user1, user2) VALUES (?, ?)")
+        .bind(user_id)
+        .bind(friend_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Remove Friend
+/// Removes `friend_id` from the user's friends.
+pub async fn remove_friend(db: &Database, user_id: i32, friend_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM users_relationships WHERE user1 = ? AND user2 = ?")
+        .bind(user_id)
+        .bind(friend_id)
+        .execute(&db.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// # Fetch Friends
+/// Fetches the ids of all users the given user has added as a friend.
+pub async fn fetch_friends(db: &Database, user_id: i32) -> Result<Vec<i32>, sqlx::Error> {
+    let rows: Vec<(i32,)> = sqlx::query_as("SELECT user2 FROM users_relationships WHERE user1 = ?")
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
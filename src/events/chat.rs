@@ -0,0 +1,318 @@
+use crate::packets::{
+    builders,
+    router::{PacketContext, BanchoServer},
+    rw::Message,
+};
+use crate::objects::{player::{Player, make_safe}, channel::Channel, filter::Filtered};
+use crate::db::users;
+use crate::events::moderation;
+use crate::logger;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Handles the player joining a channel.
+pub async fn handle_channel_join(ctx: &mut PacketContext<'_>, name: String) {
+    let packet = if join_channel(ctx.server, &ctx.player, &name).await {
+        builders::channel_join_success(&name)
+    } else {
+        builders::channel_kick(&name)
+    };
+
+    ctx.player.read().await.queue.enqueue(packet).await;
+}
+
+/// Handles the player leaving a channel.
+pub async fn handle_channel_part(ctx: &mut PacketContext<'_>, name: String) {
+    part_channel(ctx.server, &ctx.player, &name).await;
+}
+
+/// # Join Channel
+/// Adds the player to a channel, if it exists and they may read it. Returns
+/// whether the player is now in the channel.
+pub async fn join_channel(srv: &BanchoServer, player: &Arc<RwLock<Player>>, name: &str) -> bool {
+    let channel = match srv.channels.get(name) {
+        Some(c) => c,
+        None => {
+            logger::debug(format!("Tried to join the non-existent channel {}", name));
+            return false;
+        }
+    };
+    let (p_id, privileges) = {
+        let p = player.read().await;
+        (p.id, p.privileges)
+    };
+
+    {
+        let mut c = channel.write().await;
+        if !c.can_read(&privileges) {
+            return false;
+        }
+        if c.players.contains(&p_id) {
+            return true;
+        }
+        c.players.push(p_id);
+    }
+
+    player.write().await.channels.push(name.to_string());
+    send_channel_info(srv, &channel).await;
+    true
+}
+
+/// # Part Channel
+/// Removes the player from a channel, if they are in it.
+pub async fn part_channel(srv: &BanchoServer, player: &Arc<RwLock<Player>>, name: &str) {
+    let p_id = {
+        let mut p = player.write().await;
+        let joined = p.channels.len();
+        p.channels.retain(|c| c != name);
+        if p.channels.len() == joined {
+            return;
+        }
+        p.id
+    };
+
+    leave(srv, p_id, name).await;
+}
+
+/// # Part All Channels
+/// Removes the player from every channel they are in.
+pub async fn part_all(srv: &BanchoServer, player: &Arc<RwLock<Player>>) {
+    let (p_id, channels) = {
+        let mut p = player.write().await;
+        (p.id, std::mem::take(&mut p.channels))
+    };
+
+    for name in channels {
+        leave(srv, p_id, &name).await;
+    }
+}
+
+/// Removes a player id from a channel's players.
+async fn leave(srv: &BanchoServer, p_id: i32, name: &str) {
+    if let Some(channel) = srv.channels.get(name) {
+        channel.write().await.players.retain(|&id| id != p_id);
+        send_channel_info(srv, &channel).await;
+    }
+}
+
+/// # Channel Listing
+/// Writes the listing of a channel, making the client join it if it is meant
+/// to be joined on login.
+pub fn channel_listing(c: &Channel) -> Vec<u8> {
+    if c.auto_join {
+        builders::channel_auto_join(&c.name, &c.topic, &c.player_count())
+    } else {
+        builders::channel_info(&c.name, &c.topic, &c.player_count())
+    }
+}
+
+/// Sends the up to date listing of a channel to everyone able to read it.
+async fn send_channel_info(srv: &BanchoServer, channel: &Arc<RwLock<Channel>>) {
+    let c = channel.read().await;
+    if c.hidden {
+        return;
+    }
+    let packet = builders::channel_info(&c.name, &c.topic, &c.player_count());
+    let public_read = c.public_read;
+    drop(c);
+
+    for player in srv.online_players.all() {
+        let p = player.read().await;
+        if public_read || p.privileges.chat_mod() {
+            p.queue.enqueue(packet.clone()).await;
+        }
+    }
+}
+
+/// Handles the player sending a message to a channel.
+pub async fn handle_public_message(ctx: &mut PacketContext<'_>, msg: Message) {
+    if msg.content.trim().is_empty() || !may_send(ctx.server, &ctx.player).await {
+        return;
+    }
+    let content = match filter_message(ctx, &msg.content).await {
+        Some(c) => c,
+        None => return,
+    };
+    let (p_id, name, privileges, joined) = {
+        let p = ctx.player.read().await;
+        (p.id, p.name.clone(), p.privileges, p.channels.contains(&msg.target))
+    };
+
+    let channel = match ctx.server.channels.get(&msg.target) {
+        Some(c) if joined => c,
+        _ => {
+            logger::debug(format!("{} tried to message {}, which they are not in.", name, msg.target));
+            return;
+        }
+    };
+    let recipients = {
+        let c = channel.read().await;
+        if !c.can_write(&privileges) {
+            return;
+        }
+        c.players.clone()
+    };
+
+    let packet = builders::send_message(&name, &content, &msg.target, &p_id);
+    for r_id in recipients {
+        if r_id != p_id {
+            ctx.server.online_players.enqueue(r_id, packet.clone()).await;
+        }
+    }
+}
+
+/// Handles the player sending a message directly to another player.
+pub async fn handle_private_message(ctx: &mut PacketContext<'_>, msg: Message) {
+    if msg.content.trim().is_empty() || !may_send(ctx.server, &ctx.player).await {
+        return;
+    }
+    let content = match filter_message(ctx, &msg.content).await {
+        Some(c) => c,
+        None => return,
+    };
+    let (p_id, name) = {
+        let p = ctx.player.read().await;
+        (p.id, p.name.clone())
+    };
+
+    let target = match ctx.server.online_players.get_by_name(&msg.target) {
+        Some(t) => t,
+        None => {
+            logger::debug(format!("{} tried to message {}, who is not online.", name, msg.target));
+            return;
+        }
+    };
+    let t = target.read().await;
+
+    let reply = if !t.allow_dms && !t.friends.contains(&p_id) {
+        builders::user_dm_blocked(&t.name)
+    } else if t.silenced() {
+        builders::target_is_silenced(&t.name)
+    } else {
+        t.queue.enqueue(builders::send_message(&name, &content, &t.name, &p_id)).await;
+
+        match &t.away_message {
+            Some(away) => builders::send_message(&t.name, away, &name, &t.id),
+            None => return,
+        }
+    };
+    drop(t);
+
+    ctx.player.read().await.queue.enqueue(reply).await;
+}
+
+/// Runs a message through the word filter, returning what should be sent. A
+/// blocked message is not sent and the sender is warned instead. Staff
+/// commands are run rather than sent.
+async fn filter_message(ctx: &PacketContext<'_>, content: &str) -> Option<String> {
+    if run_command(ctx, content).await {
+        return None;
+    }
+
+    match ctx.server.filter.apply(content).await {
+        Filtered::Allow(c) => Some(c),
+        Filtered::Block(warning) => {
+            ctx.player.read().await.queue.enqueue(builders::notification(&warning)).await;
+            None
+        },
+    }
+}
+
+/// Runs a staff chat command, returning whether the message was one. The
+/// outcome is shown to the player as a notification.
+async fn run_command(ctx: &PacketContext<'_>, content: &str) -> bool {
+    let privileges = ctx.player.read().await.privileges;
+    let args: Vec<&str> = content.split_whitespace().collect();
+
+    let reply = match args.as_slice() {
+        ["!filter", "reload"] if privileges.chat_mod() => reload_filter(ctx.server).await,
+        ["!silence", name, seconds, reason @ ..] if privileges.silence_users() => {
+            silence_command(ctx, name, seconds, &reason.join(" ")).await
+        },
+        ["!unsilence", name] if privileges.silence_users() => unsilence_command(ctx, name).await,
+        _ => return false,
+    };
+
+    ctx.player.read().await.queue.enqueue(builders::notification(&reply)).await;
+    true
+}
+
+/// Reloads the word filter rules from the filter file.
+async fn reload_filter(srv: &BanchoServer) -> String {
+    match srv.filter.load(&srv.config.filter_file).await {
+        Ok(count) => format!("Reloaded {} word filter rules.", count),
+        Err(e) => {
+            logger::error(format!("Failed to reload the word filter: {}", e));
+            format!("Failed to reload the word filter: {}", e)
+        }
+    }
+}
+
+/// Looks a user up by name, whether they are online or not.
+async fn find_user_id(srv: &BanchoServer, name: &str) -> Result<Option<i32>, sqlx::Error> {
+    Ok(users::fetch_by_safe_name(&srv.db, &make_safe(name)).await?.map(|u| u.id))
+}
+
+/// `!silence <name> <seconds> <reason>`
+async fn silence_command(ctx: &PacketContext<'_>, name: &str, seconds: &str, reason: &str) -> String {
+    let seconds = match seconds.parse::<i32>() {
+        Ok(s) if s > 0 && !reason.is_empty() => s,
+        _ => return "Usage: !silence <name> <seconds> <reason>".to_string(),
+    };
+    let by = ctx.player.read().await.id;
+
+    match find_user_id(ctx.server, name).await {
+        Ok(Some(user_id)) => match moderation::silence(ctx.server, user_id, seconds, reason, by).await {
+            Ok(()) => format!("{} has been silenced for {} seconds.", name, seconds),
+            Err(e) => format!("Failed to silence {}: {}", name, e),
+        },
+        Ok(None) => format!("{} could not be found.", name),
+        Err(e) => format!("Failed to look {} up: {}", name, e),
+    }
+}
+
+/// `!unsilence <name>`
+async fn unsilence_command(ctx: &PacketContext<'_>, name: &str) -> String {
+    let by = ctx.player.read().await.id;
+
+    match find_user_id(ctx.server, name).await {
+        Ok(Some(user_id)) => match moderation::unsilence(ctx.server, user_id, by).await {
+            Ok(()) => format!("{} has been unsilenced.", name),
+            Err(e) => format!("Failed to unsilence {}: {}", name, e),
+        },
+        Ok(None) => format!("{} could not be found.", name),
+        Err(e) => format!("Failed to look {} up: {}", name, e),
+    }
+}
+
+/// Checks whether the player may send a message, counting it towards their
+/// spam limit. Players going over the limit are silenced, for longer with each
+/// offence.
+async fn may_send(srv: &BanchoServer, player: &Arc<RwLock<Player>>) -> bool {
+    let conf = &srv.config;
+    let (p_id, offences) = {
+        let mut p = player.write().await;
+        if p.silenced() {
+            p.queue.enqueue(builders::silence_end(&p.silence_remaining())).await;
+            return false;
+        }
+
+        let window = Duration::from_secs(conf.spam_window);
+        if conf.spam_message_limit == 0
+            || !p.message_rate.record(Instant::now(), conf.spam_message_limit, window) {
+            return true;
+        }
+        (p.id, p.message_rate.offences)
+    };
+
+    let seconds = match conf.spam_silence_durations.get(offences - 1).or_else(|| conf.spam_silence_durations.last()) {
+        Some(&s) => s,
+        None => return false,
+    };
+    let reason = format!("Spamming (auto, offence {})", offences);
+    if let Err(e) = moderation::silence(srv, p_id, seconds, &reason, conf.server_bot_id).await {
+        logger::error(format!("Failed to silence {} for spamming: {}", p_id, e));
+    }
+    false
+}
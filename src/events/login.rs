@@ -1,14 +1,26 @@
 use crate::web::server::RequestContext;
-use crate::packets::router::BanchoServer;
+use crate::packets::{builders, router::BanchoServer, rw::SimplePacketQueue};
+use crate::objects::player::{Player, Geolocation, Action, ByteQueue, MessageRate, make_safe};
+use crate::objects::version::ClientVersion;
+use crate::config::{Config, MultiAccountAction};
+use crate::consts::{
+    privileges::Privileges,
+    modes::{Mode, CustomMode},
+    countries,
+};
+use crate::db::{users, hardware, logs};
+use crate::events::{logout, chat};
 use crate::logger;
+use std::time::Instant;
 
 /// A structure of the data provided in a login request.
-struct LoginData {
+pub struct LoginData {
     username: String,
     password_md5: String,
     osu_version: String,
-    timezone: u8,
+    timezone: i8,
     allow_dms: bool,
+    #[allow(dead_code)]
     osu_hash: String,
     adapter_hash: String,
     uninstaller_hash: String,
@@ -19,7 +31,7 @@ impl LoginData {
     /// Attempts to parse the login data into a structure. On fail, returns
     /// `None`.
     pub fn from_body(body: String) -> Option<Self> {
-        let body_parts: Vec<_> = body.split("\n").collect();
+        let body_parts: Vec<_> = body.split('\n').collect();
 
         if body_parts.len() != 4 {
             logger::debug("Received invalid main body size.");
@@ -29,7 +41,7 @@ impl LoginData {
         let username = body_parts[0];
         let password_md5 = body_parts[1];
 
-        let s_data: Vec<_> = body_parts[2].split("|").collect();
+        let s_data: Vec<_> = body_parts[2].split('|').collect();
 
         if s_data.len() != 5 {
             logger::debug("Received invalid s_data size.");
@@ -37,10 +49,11 @@ impl LoginData {
         }
 
         let osu_version = s_data[0];
-        let timezone: u8 = s_data[1].parse().unwrap_or(0);
-        let allow_dms: bool = s_data[4].parse().unwrap_or(true);
+        let timezone: i8 = s_data[1].parse().unwrap_or(0);
+        // The client sends whether it blocks DMs from non-friends.
+        let allow_dms = s_data[4] != "1";
 
-        let client_hashes: Vec<_> = s_data[3].split(":").collect();
+        let client_hashes: Vec<_> = s_data[3].split(':').collect();
 
         if client_hashes.len() != 6 {
             logger::debug("Received invalid client_hashes size.");
@@ -57,17 +70,391 @@ impl LoginData {
             password_md5: password_md5.to_string(),
             osu_version: osu_version.to_string(),
             adapter_hash: adapter_hash.to_string(),
-            allow_dms: allow_dms,
+            allow_dms,
             osu_hash: osu_hash.to_string(),
             serial_hash: serial_hash.to_string(),
-            timezone: timezone,
+            timezone,
             uninstaller_hash: osu_uninst_hash.to_string(),
         })
     }
 }
 
+/// Verifies the client's password md5 against the bcrypt hash stored in the
+/// database. Done on the blocking pool as bcrypt is slow by design.
+async fn verify_password(password_md5: String, bcrypt_hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        bcrypt::verify(password_md5, &bcrypt_hash).unwrap_or(false)
+    }).await.unwrap_or(false)
+}
+
+/// The reasons a login attempt may be rejected for.
+pub enum LoginError {
+    BadCredentials,
+    Banned,
+    Locked,
+    OutdatedClient,
+    UnsupportedStream,
+    ServerError,
+    NeedSupporter,
+    MultiAccount,
+}
+
+impl LoginError {
+    /// # Reply Code
+    /// The negative user id sent to the client in place of a real one, which
+    /// the client uses to tell why the login failed.
+    pub fn reply_code(&self) -> i32 {
+        match self {
+            Self::BadCredentials | Self::UnsupportedStream | Self::MultiAccount => -1,
+            Self::OutdatedClient => -2,
+            Self::Banned => -3,
+            Self::Locked => -4,
+            Self::ServerError => -5,
+            Self::NeedSupporter => -6,
+        }
+    }
+
+    /// # Failure Message
+    /// A notification explaining the failure, for the cases where the client
+    /// does not explain it by itself.
+    pub fn message(&self) -> Option<&'static str> {
+        match self {
+            Self::Banned => Some("You are banned from this server."),
+            Self::Locked => Some(
+                "Your account is locked. Please contact a staff member to have it unlocked."
+            ),
+            Self::UnsupportedStream => Some(
+                "Your client's release stream is not supported by this server."
+            ),
+            Self::MultiAccount => Some(
+                "This computer is linked to another account. Please contact a staff member if this is a mistake."
+            ),
+            Self::ServerError => Some(
+                "The server could not process your login. Please try again later."
+            ),
+            _ => None,
+        }
+    }
+
+    /// # Failure Response
+    /// Builds the packets sent to the client for this failure.
+    pub fn response(&self) -> Vec<u8> {
+        let mut resp = SimplePacketQueue::new();
+        if let Some(msg) = self.message() {
+            resp.queue(builders::notification(msg));
+        }
+        if let Self::OutdatedClient = self {
+            resp.queue(builders::version_update_forced());
+        }
+        resp.queue(builders::login_reply(&self.reply_code()));
+        resp.into_bytes()
+    }
+}
+
+/// Checks the client's version string against the version rules in the
+/// config, returning the parsed version.
+fn check_version(version: &str, conf: &Config) -> Result<ClientVersion, LoginError> {
+    if conf.blocked_versions.iter().any(|v| v == version) {
+        return Err(LoginError::OutdatedClient);
+    }
+
+    let v = ClientVersion::parse(version).ok_or(LoginError::OutdatedClient)?;
+    if v.date < conf.min_client_build {
+        return Err(LoginError::OutdatedClient);
+    }
+    if !conf.allowed_streams.contains(&v.stream) {
+        return Err(LoginError::UnsupportedStream);
+    }
+
+    Ok(v)
+}
+
+// Wine does not expose real hardware, so all wine users share these hashes.
+// The adapters hash is md5("runningunderwine").
+const WINE_ADAPTERS_HASH: &str = "b4ec3c4334a0249dae95c284ec5983df";
+const WINE_SERIAL_HASH: &str = "ffae06fb022871fe9beb58b005c5e21d";
+
+/// Checks if the client is running under wine, going by its hardware hashes.
+fn running_under_wine(data: &LoginData) -> bool {
+    data.adapter_hash == WINE_ADAPTERS_HASH || data.serial_hash == WINE_SERIAL_HASH
+}
+
+/// Logs the client's hardware hashes and checks them against those of other
+/// accounts, taking the action set in the config if they are shared.
+async fn check_hardware(
+    srv: &BanchoServer,
+    user_id: i32,
+    username: &str,
+    data: &LoginData,
+    privileges: &mut Privileges,
+) -> Result<(), LoginError> {
+    if let Err(e) = hardware::log_login(
+        &srv.db, user_id, &data.adapter_hash, &data.uninstaller_hash, &data.serial_hash
+    ).await {
+        logger::error(format!("Failed to log hardware for {}: {}", username, e));
+    }
+
+    // Wine clients all send the same adapters and disk hashes, so only their
+    // uninstaller hash tells them apart.
+    let matches = if running_under_wine(data) {
+        hardware::fetch_unique_id_matches(&srv.db, user_id, &data.uninstaller_hash).await
+    } else {
+        hardware::fetch_matches(&srv.db, user_id, &data.uninstaller_hash, &data.serial_hash).await
+    };
+    let matches = match matches {
+        Ok(m) => m,
+        Err(e) => {
+            logger::error(format!("Failed to check hardware for {}: {}", username, e));
+            return Ok(());
+        }
+    };
+    if matches.is_empty() {
+        return Ok(());
+    }
+
+    let others = matches.iter()
+        .map(|m| format!("{} ({})", m.username, m.id))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let msg = format!("{} ({}) shares hardware with {}.", username, user_id, others);
+    logger::warn(&msg);
+    srv.notify_staff(&msg).await;
+
+    // Only act on the newer account, the original keeps working.
+    if !matches.iter().any(|m| m.id < user_id) {
+        return Ok(());
+    }
+
+    match srv.config.multi_account_action {
+        MultiAccountAction::Log => Ok(()),
+        MultiAccountAction::Block => Err(LoginError::MultiAccount),
+        MultiAccountAction::Restrict => {
+            if !privileges.public() {
+                return Ok(());
+            }
+            if let Err(e) = users::restrict(&srv.db, user_id).await {
+                logger::error(format!("Failed to restrict {}: {}", username, e));
+                return Ok(());
+            }
+            privileges.restrict();
+
+            let log = format!("has restricted {} for multi-accounting ({}).", username, others);
+            if let Err(e) = logs::rap_log(&srv.db, srv.config.server_bot_id, &log).await {
+                logger::error(format!("Failed to log restriction of {}: {}", username, e));
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Handles the action of logging into the server
 pub async fn login_handle(req: &mut RequestContext, srv: &BanchoServer) -> (String, Vec<u8>) {
-    let login_data = LoginData::from_body(req.read_string().await);
-    ("no".to_string(), vec![])
+    match login(req, srv).await {
+        Ok(resp) => resp,
+        Err(e) => ("no".to_string(), e.response()),
+    }
+}
+
+/// Authenticates the user and sets up their session, returning their token and
+/// the login packets.
+async fn login(req: &mut RequestContext, srv: &BanchoServer) -> Result<(String, Vec<u8>), LoginError> {
+    let login_data = LoginData::from_body(req.read_string().await)
+        .ok_or(LoginError::ServerError)?;
+
+    let version = match check_version(&login_data.osu_version, &srv.config) {
+        Ok(v) => v,
+        Err(e) => {
+            logger::info(format!(
+                "{} failed to log in (disallowed client {}).", login_data.username, login_data.osu_version
+            ));
+            return Err(e);
+        }
+    };
+
+    let user = match users::fetch_by_safe_name(&srv.db, &make_safe(&login_data.username)).await {
+        Ok(Some(u)) => u,
+        Ok(None) => {
+            logger::info(format!("Login attempt for non-existent user {}.", login_data.username));
+            return Err(LoginError::BadCredentials);
+        },
+        Err(e) => {
+            logger::error(format!("Failed to fetch user {}: {}", login_data.username, e));
+            return Err(LoginError::ServerError);
+        }
+    };
+
+    if !verify_password(login_data.password_md5.clone(), user.password_md5).await {
+        logger::info(format!("{} failed to log in (wrong password).", user.username));
+        return Err(LoginError::BadCredentials);
+    }
+
+    // New accounts are verified on their first login, once their hardware
+    // has been checked.
+    let mut privileges = Privileges::from_bitwise(user.privileges as u32);
+    let unverified = privileges.unverified();
+    if unverified {
+        privileges.verify();
+    }
+    if privileges.banned() {
+        logger::info(format!("{} failed to log in (banned).", user.username));
+        return Err(LoginError::Banned);
+    }
+    if privileges.locked() {
+        logger::info(format!("{} failed to log in (locked).", user.username));
+        return Err(LoginError::Locked);
+    }
+
+    check_hardware(srv, user.id, &user.username, &login_data, &mut privileges).await?;
+
+    if unverified {
+        if let Err(e) = users::set_privileges(&srv.db, user.id, privileges.privs).await {
+            logger::error(format!("Failed to verify {}: {}", user.username, e));
+            return Err(LoginError::ServerError);
+        }
+        logger::info(format!("{} ({}) has been verified.", user.username, user.id));
+    }
+
+    let mode = Mode::STANDARD;
+    let (stats, friends) = match futures::try_join!(
+        users::fetch_stats(&srv.db, user.id, &mode, &CustomMode::VANILLA),
+        users::fetch_friends(&srv.db, user.id),
+    ) {
+        Ok(r) => r,
+        Err(e) => {
+            logger::error(format!("Failed to load data for {}: {}", user.username, e));
+            return Err(LoginError::ServerError);
+        }
+    };
+
+    let ip = req.get_ip();
+    let location = match srv.geoip.as_ref().and_then(|g| g.lookup(ip)) {
+        Some(loc) => {
+            if loc.country != 0 {
+                let iso_code = countries::country_code(loc.country);
+                if let Err(e) = users::set_initial_country(&srv.db, user.id, iso_code).await {
+                    logger::error(format!("Failed to set country for {}: {}", user.username, e));
+                }
+            }
+            loc
+        },
+        None => Geolocation::unknown(ip),
+    };
+
+    let token = uuid::Uuid::new_v4().to_string();
+    let p = Player {
+        id: user.id,
+        name: user.username,
+        safe_name: user.username_safe,
+        uuid: token.clone(),
+        location,
+        privileges,
+        action: Action::new(),
+        mode,
+        c_mode: CustomMode::VANILLA,
+        stats,
+        friends,
+        utc_offset: login_data.timezone,
+        allow_dms: login_data.allow_dms,
+        away_message: None,
+        tourney: version.stream == "tourney",
+        spectating: None,
+        spectators: Vec::new(),
+        match_id: None,
+        channels: Vec::new(),
+        silence_end: user.silence_end as i64,
+        message_rate: MessageRate::new(),
+        login_time: Instant::now(),
+        last_seen: Instant::now(),
+        queue: ByteQueue::new(),
+    };
+
+    let own_presence = builders::user_presence(&p);
+    let own_stats = builders::user_stats(&p);
+
+    let mut resp = SimplePacketQueue::new();
+    resp.queue(builders::protocol_version(&builders::PROTOCOL_VERSION));
+    resp.queue(builders::login_reply(&p.id));
+    resp.queue(builders::bancho_privileges(&(p.privileges.as_bancho_priv(true) as i32)));
+    resp.queue(own_presence.clone());
+    resp.queue(own_stats.clone());
+    // List the channels the player can join. The client asks to join the
+    // auto-join ones itself.
+    for channel in srv.channels.all() {
+        let c = channel.read().await;
+        if !c.hidden && c.can_read(&p.privileges) {
+            resp.queue(chat::channel_listing(&c));
+        }
+    }
+    resp.queue(builders::channel_info_end());
+    resp.queue(builders::friends_list(&p.friends));
+    resp.queue(builders::silence_end(&p.silence_remaining()));
+
+    if !p.privileges.public() {
+        resp.queue(builders::account_restricted());
+        resp.queue(builders::notification("Your account is currently in restricted mode."));
+    }
+
+    // Only one session per user, so end any previous ones. The tournament
+    // client opens several sessions on the same account, so those are let be.
+    if !p.tourney {
+        for old in srv.online_players.sessions(p.id) {
+            logout::kick(srv, &old, "Logged in from another client.").await;
+        }
+    }
+
+    // Let the new player know about everyone else.
+    for other in srv.online_players.all() {
+        let o = other.read().await;
+        if o.id == p.id { continue; }
+
+        resp.queue(builders::user_presence(&o));
+        resp.queue(builders::user_stats(&o));
+    }
+
+    // And let everyone else know about the new player.
+    let mut announce = own_presence;
+    announce.extend(own_stats);
+    srv.online_players.broadcast(announce).await;
+
+    logger::info(format!("{} ({}) has logged in.", p.name, p.id));
+    srv.online_players.add_player(p);
+
+    Ok((token, resp.into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(min_client_build: u32) -> Config {
+        Config { min_client_build, ..Config::new() }
+    }
+
+    #[test]
+    fn min_build_boundary() {
+        let conf = config(20211231);
+        assert!(check_version("b20211231", &conf).is_ok());
+        assert!(check_version("b20220101cuttingedge", &conf).is_ok());
+        assert!(matches!(check_version("b20211230.9", &conf), Err(LoginError::OutdatedClient)));
+    }
+
+    #[test]
+    fn detects_wine() {
+        let wine = include_str!("../../fuzz/corpus/login_body/runningunderwine");
+        assert!(running_under_wine(&LoginData::from_body(wine.to_string()).unwrap()));
+
+        let stable = include_str!("../../fuzz/corpus/login_body/stable");
+        assert!(!running_under_wine(&LoginData::from_body(stable.to_string()).unwrap()));
+    }
+
+    #[test]
+    fn rejected_versions() {
+        let mut conf = config(0);
+        assert!(matches!(check_version("not a version", &conf), Err(LoginError::OutdatedClient)));
+        assert!(matches!(check_version("b20211231dev", &conf), Err(LoginError::UnsupportedStream)));
+
+        conf.blocked_versions.push("b20211231".to_string());
+        assert!(matches!(check_version("b20211231", &conf), Err(LoginError::OutdatedClient)));
+        assert!(check_version("b20211231.1", &conf).is_ok());
+    }
 }
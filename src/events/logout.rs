@@ -0,0 +1,81 @@
+use crate::packets::{
+    builders,
+    router::{PacketContext, BanchoServer},
+};
+use crate::events::{spectate, multiplayer, chat};
+use crate::objects::player::Player;
+use crate::db::users;
+use crate::logger;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// How long after logging in logouts are ignored for. The client often sends
+/// a stale logout right after logging in again.
+const LOGOUT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Handles the player logging out.
+pub async fn handle_logout(ctx: &mut PacketContext<'_>) {
+    if ctx.player.read().await.login_time.elapsed() < LOGOUT_GRACE_PERIOD {
+        return;
+    }
+
+    logout(ctx.server, &ctx.player).await;
+}
+
+/// # Logout
+/// Removes the player from the server, taking them out of any match, spectator
+/// session or channel and letting everyone know they are gone. Used for every
+/// way a session can end, be it logging out, being kicked or timing out.
+pub async fn logout(srv: &BanchoServer, player: &Arc<RwLock<Player>>) {
+    spectate::stop_spectating(srv, player).await;
+    spectate::drop_spectators(srv, player).await;
+    multiplayer::leave_match(srv, player).await;
+    chat::part_all(srv, player).await;
+
+    let (p_id, name) = {
+        let p = player.read().await;
+        (p.id, p.name.clone())
+    };
+
+    srv.lobby.remove_session(p_id, player);
+    // The user may have logged in again since, in which case they are still
+    // online.
+    if !srv.online_players.remove_session(p_id, player) {
+        return;
+    }
+    // Tournament clients may still have other sessions on the account.
+    if srv.online_players.get(p_id).is_none() {
+        srv.online_players.broadcast(builders::user_logout(&p_id)).await;
+    }
+
+    if let Err(e) = users::update_latest_activity(&srv.db, p_id).await {
+        logger::error(format!("Failed to update the latest activity of {}: {}", p_id, e));
+    }
+
+    logger::info(format!("{} ({}) has logged out.", name, p_id));
+}
+
+/// How long the packets left for a kicked player are kept.
+const KICKED_PACKETS_TTL: Duration = Duration::from_secs(300);
+
+/// # Kick
+/// Forcibly ends the player's session, telling them why in case they are
+/// still connected. Their client is told to reconnect on its next request.
+pub async fn kick(srv: &BanchoServer, player: &Arc<RwLock<Player>>, reason: &str) {
+    {
+        let p = player.read().await;
+        logger::info(format!("Kicking {} ({}): {}", p.name, p.id, reason));
+        p.queue.enqueue(builders::notification(&format!("You have been kicked: {}", reason))).await;
+    }
+
+    logout(srv, player).await;
+
+    // The player is no longer reachable by their token, so hold on to what is
+    // left in their queue for their client's next request.
+    // Clients that never came back will not collect theirs.
+    srv.kicked.retain(|_, (at, _)| at.elapsed() < KICKED_PACKETS_TTL);
+    let p = player.read().await;
+    let packets = p.queue.empty().await;
+    srv.kicked.insert(p.uuid.clone(), (Instant::now(), packets));
+}
@@ -3,6 +3,6 @@ use crate::packets::{
     router::PacketContext
 };
 
-pub async fn handle_ping(ctx: &PacketContext<'_>) {
+pub async fn handle_ping(ctx: &mut PacketContext<'_>) {
     ctx.player.read().await.queue.enqueue(builders::ping()).await;
 }
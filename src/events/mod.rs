@@ -1,2 +1,73 @@
 pub mod login;
+pub mod logout;
 pub mod misc;
+pub mod user;
+pub mod spectate;
+pub mod multiplayer;
+pub mod chat;
+pub mod moderation;
+
+use crate::packets::{router::PacketContext, client::ClientPacket};
+use crate::logger;
+
+/// # Handle Packet
+/// Dispatches a single decoded packet from the client to its handler.
+pub async fn handle_packet(packet: ClientPacket, ctx: &mut PacketContext<'_>) {
+    match packet {
+        ClientPacket::Ping => misc::handle_ping(ctx).await,
+        ClientPacket::Logout => logout::handle_logout(ctx).await,
+
+        // User state.
+        ClientPacket::ChangeAction(action) => user::handle_change_action(ctx, action).await,
+        ClientPacket::RequestStatusUpdate => user::handle_request_status_update(ctx).await,
+        ClientPacket::UserStatsRequest(ids) => user::handle_stats_request(ctx, ids).await,
+        ClientPacket::UserPresenceRequest(ids) => user::handle_presence_request(ctx, ids).await,
+        ClientPacket::UserPresenceRequestAll(_) => user::handle_presence_request_all(ctx).await,
+        ClientPacket::SetAwayMessage(msg) => user::handle_set_away_message(ctx, msg).await,
+        ClientPacket::FriendAdd(friend_id) => user::handle_friend_add(ctx, friend_id).await,
+        ClientPacket::FriendRemove(friend_id) => user::handle_friend_remove(ctx, friend_id).await,
+        ClientPacket::ToggleBlockNonFriendDms(block) => user::handle_toggle_block_dms(ctx, block).await,
+
+        // Chat.
+        ClientPacket::ChannelJoin(name) => chat::handle_channel_join(ctx, name).await,
+        ClientPacket::ChannelPart(name) => chat::handle_channel_part(ctx, name).await,
+        ClientPacket::SendPublicMessage(msg) => chat::handle_public_message(ctx, msg).await,
+        ClientPacket::SendPrivateMessage(msg) => chat::handle_private_message(ctx, msg).await,
+
+        // Spectating.
+        ClientPacket::StartSpectating(host_id) => spectate::handle_start_spectating(ctx, host_id).await,
+        ClientPacket::StopSpectating => spectate::handle_stop_spectating(ctx).await,
+        ClientPacket::SpectateFrames(frames) => spectate::handle_spectate_frames(ctx, frames).await,
+        ClientPacket::CantSpectate => spectate::handle_cant_spectate(ctx).await,
+
+        // Multiplayer.
+        ClientPacket::JoinLobby => multiplayer::handle_join_lobby(ctx).await,
+        ClientPacket::PartLobby => multiplayer::handle_part_lobby(ctx).await,
+        ClientPacket::CreateMatch(data) => multiplayer::handle_create_match(ctx, data).await,
+        ClientPacket::JoinMatch(join) => multiplayer::handle_join_match(ctx, join).await,
+        ClientPacket::PartMatch => multiplayer::handle_part_match(ctx).await,
+        ClientPacket::MatchChangeSlot(slot_id) => multiplayer::handle_change_slot(ctx, slot_id).await,
+        ClientPacket::MatchReady => multiplayer::handle_ready(ctx).await,
+        ClientPacket::MatchUnready => multiplayer::handle_unready(ctx).await,
+        ClientPacket::MatchLock(slot_id) => multiplayer::handle_lock(ctx, slot_id).await,
+        ClientPacket::MatchChangeSettings(data) => multiplayer::handle_change_settings(ctx, data).await,
+        ClientPacket::MatchChangeMods(mods) => multiplayer::handle_change_mods(ctx, mods).await,
+        ClientPacket::MatchChangeTeam => multiplayer::handle_change_team(ctx).await,
+        ClientPacket::MatchStart => multiplayer::handle_start(ctx).await,
+        ClientPacket::MatchLoadComplete => multiplayer::handle_load_complete(ctx).await,
+        ClientPacket::MatchScoreUpdate(frame) => multiplayer::handle_score_update(ctx, frame).await,
+        ClientPacket::MatchComplete => multiplayer::handle_complete(ctx).await,
+        ClientPacket::MatchFailed => multiplayer::handle_failed(ctx).await,
+        ClientPacket::MatchNoBeatmap => multiplayer::handle_no_beatmap(ctx).await,
+        ClientPacket::MatchHasBeatmap => multiplayer::handle_has_beatmap(ctx).await,
+        ClientPacket::MatchSkipRequest => multiplayer::handle_skip_request(ctx).await,
+        ClientPacket::MatchTransferHost(slot_id) => multiplayer::handle_transfer_host(ctx, slot_id).await,
+        ClientPacket::MatchChangePassword(data) => multiplayer::handle_change_password(ctx, data).await,
+        ClientPacket::MatchInvite(target_id) => multiplayer::handle_invite(ctx, target_id).await,
+
+        // Packets we knowingly ignore.
+        ClientPacket::ReceiveUpdates(_) | ClientPacket::ErrorReport(_) => {},
+
+        packet => logger::debug(format!("No handler for packet with id {}", packet.id())),
+    }
+}
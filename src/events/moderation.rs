@@ -0,0 +1,49 @@
+use crate::packets::{builders, router::BanchoServer};
+use crate::objects::player::unix_time;
+use crate::db::{users, logs};
+use crate::logger;
+
+/// # Silence
+/// Silences a user for `seconds`, storing the reason and logging who (`by`)
+/// silenced them. If the user is online, their client is told and everyone
+/// clears their messages from chat.
+pub async fn silence(srv: &BanchoServer, user_id: i32, seconds: i32, reason: &str, by: i32) -> Result<(), sqlx::Error> {
+    let silence_end = unix_time() + seconds as i64;
+    users::set_silence(&srv.db, user_id, silence_end, reason).await?;
+
+    if let Some(player) = srv.online_players.get(user_id) {
+        {
+            let mut p = player.write().await;
+            p.silence_end = silence_end;
+            p.queue.enqueue(builders::silence_end(&seconds)).await;
+        }
+        srv.online_players.broadcast(builders::user_silenced(&user_id)).await;
+    }
+
+    let log = format!("has silenced user {} for {} seconds for the following reason: \"{}\"", user_id, seconds, reason);
+    if let Err(e) = logs::rap_log(&srv.db, by, &log).await {
+        logger::error(format!("Failed to log the silence of {}: {}", user_id, e));
+    }
+
+    logger::info(format!("User {} has been silenced for {} seconds: {}", user_id, seconds, reason));
+    Ok(())
+}
+
+/// # Unsilence
+/// Lifts a user's silence, logging who (`by`) lifted it.
+pub async fn unsilence(srv: &BanchoServer, user_id: i32, by: i32) -> Result<(), sqlx::Error> {
+    users::set_silence(&srv.db, user_id, 0, "").await?;
+
+    if let Some(player) = srv.online_players.get(user_id) {
+        let mut p = player.write().await;
+        p.silence_end = 0;
+        p.queue.enqueue(builders::silence_end(&0)).await;
+    }
+
+    if let Err(e) = logs::rap_log(&srv.db, by, &format!("has removed the silence of user {}", user_id)).await {
+        logger::error(format!("Failed to log the unsilence of {}: {}", user_id, e));
+    }
+
+    logger::info(format!("User {} has been unsilenced.", user_id));
+    Ok(())
+}
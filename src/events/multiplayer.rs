@@ -0,0 +1,598 @@
+use crate::packets::{
+    builders,
+    router::{PacketContext, BanchoServer},
+    rw::SimplePacketQueue,
+    packet::{MatchData, Raw},
+    client::JoinMatch,
+};
+use crate::objects::player::Player;
+use crate::objects::multiplayer::*;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Takes the settings out of the match structure sent by the client when
+/// creating or editing a match.
+fn match_settings(data: MatchData) -> MatchSettings {
+    MatchSettings {
+        name: data.name,
+        password: data.password,
+        mods: data.mods,
+        map_name: data.map_name,
+        map_id: data.map_id,
+        map_md5: data.map_md5,
+        host: data.host,
+        mode: data.mode,
+        win_condition: data.win_condition,
+        team_type: data.team_type,
+        freemods: data.freemods,
+        seed: data.seed,
+    }
+}
+
+/// Fetches the id of the player alongside the match they are in, if any.
+async fn current_match(ctx: &PacketContext<'_>) -> Option<(i32, Arc<RwLock<Match>>)> {
+    let (p_id, match_id) = {
+        let p = ctx.player.read().await;
+        (p.id, p.match_id?)
+    };
+
+    Some((p_id, ctx.server.matches.get(match_id).await?))
+}
+
+/// # Send Match Update
+/// Sends the current state of a match to its players and to the lobby.
+pub async fn send_match_update(srv: &BanchoServer, m: &Match) {
+    let packet = builders::update_match(m, true);
+    for p_id in m.players() {
+        srv.online_players.enqueue(p_id, packet.clone()).await;
+    }
+
+    srv.lobby.broadcast(builders::update_match(m, false)).await;
+}
+
+/// Queues a packet to all players in the match with any of the given slot
+/// statuses.
+async fn enqueue_with_status(srv: &BanchoServer, m: &Match, status: u8, packet: Vec<u8>) {
+    for p_id in m.players_with_status(status) {
+        srv.online_players.enqueue(p_id, packet.clone()).await;
+    }
+}
+
+/// Handles the player entering the multiplayer lobby.
+pub async fn handle_join_lobby(ctx: &mut PacketContext<'_>) {
+    ctx.server.lobby.add(ctx.player.clone()).await;
+
+    let mut q = SimplePacketQueue::new();
+    for m in ctx.server.matches.all().await {
+        q.queue(builders::new_match(&*m.read().await));
+    }
+    ctx.player.read().await.queue.enqueue(q.into_bytes()).await;
+}
+
+/// Handles the player leaving the multiplayer lobby.
+pub async fn handle_part_lobby(ctx: &mut PacketContext<'_>) {
+    let p_id = ctx.player.read().await.id;
+    ctx.server.lobby.remove(p_id);
+}
+
+/// Handles the player creating a new match.
+pub async fn handle_create_match(ctx: &mut PacketContext<'_>, data: MatchData) {
+    let mut settings = match_settings(data);
+    leave_match(ctx.server, &ctx.player).await;
+
+    let p_id = ctx.player.read().await.id;
+    settings.host = p_id;
+
+    let m = match ctx.server.matches.create(settings).await {
+        Some(m) => m,
+        None => {
+            ctx.player.read().await.queue.enqueue(builders::match_join_fail()).await;
+            return;
+        }
+    };
+    let (match_id, join_success, new_match) = {
+        let mut m = m.write().await;
+        m.place_player(0, p_id);
+        (m.id, builders::match_join_success(&m), builders::new_match(&m))
+    };
+
+    ctx.player.write().await.match_id = Some(match_id);
+    ctx.player.read().await.queue.enqueue(join_success).await;
+    ctx.server.lobby.broadcast(new_match).await;
+}
+
+/// Handles the player joining an existing match.
+pub async fn handle_join_match(ctx: &mut PacketContext<'_>, join: JoinMatch) {
+    let JoinMatch { match_id, password } = join;
+    leave_match(ctx.server, &ctx.player).await;
+
+    let p_id = ctx.player.read().await.id;
+    let m = match ctx.server.matches.get(match_id).await {
+        Some(m) => m,
+        None => {
+            ctx.player.read().await.queue.enqueue(builders::match_join_fail()).await;
+            return;
+        }
+    };
+
+    let join_success = {
+        let mut m = m.write().await;
+        let slot = m.free_slot().filter(|_| m.password.is_empty() || m.password == password);
+        slot.map(|slot| {
+            m.place_player(slot, p_id);
+            builders::match_join_success(&m)
+        })
+    };
+
+    match join_success {
+        Some(packet) => {
+            ctx.player.write().await.match_id = Some(match_id);
+            ctx.player.read().await.queue.enqueue(packet).await;
+            send_match_update(ctx.server, &*m.read().await).await;
+        },
+        None => ctx.player.read().await.queue.enqueue(builders::match_join_fail()).await,
+    }
+}
+
+/// Handles the player leaving their match.
+pub async fn handle_part_match(ctx: &mut PacketContext<'_>) {
+    leave_match(ctx.server, &ctx.player).await;
+}
+
+/// # Leave Match
+/// Removes the player from their match, if any. The host is passed on to the
+/// next player and the match is disposed of once empty.
+pub async fn leave_match(srv: &BanchoServer, player: &Arc<RwLock<Player>>) {
+    let (p_id, match_id) = {
+        let mut p = player.write().await;
+        match p.match_id.take() {
+            Some(match_id) => (p.id, match_id),
+            None => return,
+        }
+    };
+
+    let m = match srv.matches.get(match_id).await {
+        Some(m) => m,
+        None => return,
+    };
+    let mut m = m.write().await;
+
+    let left = match m.slot_of(p_id) {
+        Some(slot) => {
+            let left = m.slots[slot];
+            m.slots[slot].clear();
+            Some(left)
+        },
+        None => None,
+    };
+
+    let players = m.players();
+    if players.is_empty() {
+        srv.matches.remove(match_id).await;
+        srv.lobby.broadcast(builders::dispose_match(&match_id)).await;
+        return;
+    }
+
+    if m.host == p_id {
+        m.host = players[0];
+        srv.online_players.enqueue(m.host, builders::match_transfer_host()).await;
+    }
+
+    // The player may have been the last one the others were waiting on.
+    if let Some(left) = left.filter(|s| m.in_progress && s.status == SLOT_PLAYING) {
+        if !finish_if_done(srv, &mut m).await {
+            if !left.loaded {
+                check_all_loaded(srv, &m).await;
+            }
+            if !left.skipped {
+                check_all_skipped(srv, &m).await;
+            }
+        }
+    }
+
+    send_match_update(srv, &m).await;
+}
+
+/// Tells the playing players to start once all of them have loaded the map.
+async fn check_all_loaded(srv: &BanchoServer, m: &Match) {
+    if m.all_playing(|s| s.loaded) {
+        enqueue_with_status(srv, m, SLOT_PLAYING, builders::match_all_players_loaded()).await;
+    }
+}
+
+/// Skips the map's intro once all of the playing players have asked to.
+async fn check_all_skipped(srv: &BanchoServer, m: &Match) {
+    if m.all_playing(|s| s.skipped) {
+        enqueue_with_status(srv, m, SLOT_PLAYING, builders::match_skip()).await;
+    }
+}
+
+/// Wraps the match up once no one is still playing, aborting it if no one
+/// finished. Returns whether the match has ended.
+async fn finish_if_done(srv: &BanchoServer, m: &mut Match) -> bool {
+    if m.slots.iter().any(|s| s.status == SLOT_PLAYING) {
+        return false;
+    }
+
+    let finished = m.players_with_status(SLOT_COMPLETE);
+    m.in_progress = false;
+    for slot in m.slots.iter_mut().filter(|s| s.status == SLOT_COMPLETE) {
+        slot.status = SLOT_NOT_READY;
+        slot.loaded = false;
+        slot.skipped = false;
+    }
+
+    if finished.is_empty() {
+        let abort = builders::match_abort();
+        for player in m.players() {
+            srv.online_players.enqueue(player, abort.clone()).await;
+        }
+    } else {
+        let complete = builders::match_complete();
+        for player in finished {
+            srv.online_players.enqueue(player, complete.clone()).await;
+        }
+    }
+    true
+}
+
+/// Handles the player moving to another slot.
+pub async fn handle_change_slot(ctx: &mut PacketContext<'_>, slot_id: i32) {
+    let slot_id = slot_id as usize;
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.in_progress || slot_id >= MAX_SLOTS || m.slots[slot_id].status != SLOT_OPEN {
+        return;
+    }
+    let old_slot = match m.slot_of(p_id) {
+        Some(s) => s,
+        None => return,
+    };
+
+    m.slots[slot_id] = m.slots[old_slot];
+    m.slots[old_slot].clear();
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Sets the status of the player's own slot.
+async fn set_own_status(ctx: &PacketContext<'_>, status: u8) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if let Some(slot) = m.slot_of(p_id) {
+        m.slots[slot].status = status;
+        send_match_update(ctx.server, &m).await;
+    }
+}
+
+/// Handles the player readying up.
+pub async fn handle_ready(ctx: &mut PacketContext<'_>) {
+    set_own_status(ctx, SLOT_READY).await;
+}
+
+/// Handles the player unreadying.
+pub async fn handle_unready(ctx: &mut PacketContext<'_>) {
+    set_own_status(ctx, SLOT_NOT_READY).await;
+}
+
+/// Handles the player not having the selected map.
+pub async fn handle_no_beatmap(ctx: &mut PacketContext<'_>) {
+    set_own_status(ctx, SLOT_NO_MAP).await;
+}
+
+/// Handles the player having obtained the selected map.
+pub async fn handle_has_beatmap(ctx: &mut PacketContext<'_>) {
+    set_own_status(ctx, SLOT_NOT_READY).await;
+}
+
+/// Handles the host locking or unlocking an empty slot.
+pub async fn handle_lock(ctx: &mut PacketContext<'_>, slot_id: i32) {
+    let slot_id = slot_id as usize;
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.host != p_id || slot_id >= MAX_SLOTS {
+        return;
+    }
+
+    let slot = &mut m.slots[slot_id];
+    slot.status = match slot.status {
+        SLOT_OPEN => SLOT_LOCKED,
+        SLOT_LOCKED => SLOT_OPEN,
+        _ => return,
+    };
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the host changing the settings of the match.
+pub async fn handle_change_settings(ctx: &mut PacketContext<'_>, data: MatchData) {
+    let settings = match_settings(data);
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.host != p_id {
+        return;
+    }
+
+    if m.map_md5 != settings.map_md5 {
+        m.unready_players();
+    }
+
+    if m.freemods != settings.freemods {
+        if settings.freemods {
+            // Everyone keeps the current mods, bar the speed mods.
+            let player_mods = m.mods & !SPEED_MODS;
+            for slot in m.slots.iter_mut().filter(|s| s.has_player()) {
+                slot.mods = player_mods;
+            }
+            m.mods &= SPEED_MODS;
+        } else {
+            // The host's mods become the match's.
+            let host_mods = m.slot_of(m.host).map(|s| m.slots[s].mods).unwrap_or(0);
+            m.mods = host_mods | (m.mods & SPEED_MODS);
+            for slot in m.slots.iter_mut() {
+                slot.mods = 0;
+            }
+        }
+    }
+
+    if m.team_type != settings.team_type {
+        let team = if settings.team_type == TEAM_TYPE_TEAM_VS || settings.team_type == TEAM_TYPE_TAG_TEAM_VS {
+            TEAM_RED
+        } else {
+            TEAM_NEUTRAL
+        };
+        for slot in m.slots.iter_mut().filter(|s| s.has_player()) {
+            slot.team = team;
+        }
+    }
+
+    m.name = settings.name;
+    m.map_name = settings.map_name;
+    m.map_id = settings.map_id;
+    m.map_md5 = settings.map_md5;
+    m.mode = settings.mode;
+    m.win_condition = settings.win_condition;
+    m.team_type = settings.team_type;
+    m.freemods = settings.freemods;
+    m.seed = settings.seed;
+
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the player changing their mods.
+pub async fn handle_change_mods(ctx: &mut PacketContext<'_>, mods: u32) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.freemods {
+        if m.host == p_id {
+            m.mods = mods & SPEED_MODS;
+        }
+        if let Some(slot) = m.slot_of(p_id) {
+            m.slots[slot].mods = mods & !SPEED_MODS;
+        }
+    } else if m.host == p_id {
+        m.mods = mods;
+    } else {
+        return;
+    }
+
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the player changing their team.
+pub async fn handle_change_team(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if !m.is_team_match() {
+        return;
+    }
+    if let Some(slot) = m.slot_of(p_id) {
+        let slot = &mut m.slots[slot];
+        slot.team = if slot.team == TEAM_BLUE { TEAM_RED } else { TEAM_BLUE };
+        send_match_update(ctx.server, &m).await;
+    }
+}
+
+/// Handles the host starting the match.
+pub async fn handle_start(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.host != p_id {
+        return;
+    }
+
+    for slot in m.slots.iter_mut() {
+        if slot.has_player() && slot.status != SLOT_NO_MAP {
+            slot.status = SLOT_PLAYING;
+            slot.loaded = false;
+            slot.skipped = false;
+        }
+    }
+    m.in_progress = true;
+
+    enqueue_with_status(ctx.server, &m, SLOT_PLAYING, builders::match_start(&m)).await;
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the player having loaded the map.
+pub async fn handle_load_complete(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    let slot = match m.slot_of(p_id) {
+        Some(s) => s,
+        None => return,
+    };
+    m.slots[slot].loaded = true;
+
+    check_all_loaded(ctx.server, &m).await;
+}
+
+/// Handles the player sending their current score, relaying it to the match.
+pub async fn handle_score_update(ctx: &mut PacketContext<'_>, frame: Raw) {
+    let Raw(mut frame) = frame;
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let m = m.read().await;
+    let slot = match m.slot_of(p_id) {
+        Some(s) => s,
+        None => return,
+    };
+
+    // The 5th byte of the frame is the slot the score belongs to.
+    if frame.len() < 5 {
+        return;
+    }
+    frame[4] = slot as u8;
+
+    let packet = builders::match_score_update(&frame);
+    for player in m.players() {
+        ctx.server.online_players.enqueue(player, packet.clone()).await;
+    }
+}
+
+/// Handles the player finishing the map.
+pub async fn handle_complete(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if let Some(slot) = m.slot_of(p_id) {
+        m.slots[slot].status = SLOT_COMPLETE;
+    }
+
+    if finish_if_done(ctx.server, &mut m).await {
+        send_match_update(ctx.server, &m).await;
+    }
+}
+
+/// Handles the player failing the map.
+pub async fn handle_failed(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let m = m.read().await;
+    if let Some(slot) = m.slot_of(p_id) {
+        let packet = builders::match_player_failed(&(slot as i32));
+        enqueue_with_status(ctx.server, &m, SLOT_PLAYING, packet).await;
+    }
+}
+
+/// Handles the player requesting to skip the map's intro.
+pub async fn handle_skip_request(ctx: &mut PacketContext<'_>) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    let slot = match m.slot_of(p_id) {
+        Some(s) => s,
+        None => return,
+    };
+    m.slots[slot].skipped = true;
+
+    let packet = builders::match_player_skipped(&(slot as i32));
+    enqueue_with_status(ctx.server, &m, SLOT_PLAYING, packet).await;
+
+    check_all_skipped(ctx.server, &m).await;
+}
+
+/// Handles the host passing the host to the player in another slot.
+pub async fn handle_transfer_host(ctx: &mut PacketContext<'_>, slot_id: i32) {
+    let slot_id = slot_id as usize;
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.host != p_id || slot_id >= MAX_SLOTS {
+        return;
+    }
+    let new_host = match m.slots[slot_id].player {
+        Some(h) => h,
+        None => return,
+    };
+
+    m.host = new_host;
+    ctx.server.online_players.enqueue(new_host, builders::match_transfer_host()).await;
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the host changing the password of the match.
+pub async fn handle_change_password(ctx: &mut PacketContext<'_>, data: MatchData) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+
+    let mut m = m.write().await;
+    if m.host != p_id {
+        return;
+    }
+    m.password = data.password;
+
+    let packet = builders::match_change_password(&m.password);
+    for player in m.players() {
+        ctx.server.online_players.enqueue(player, packet.clone()).await;
+    }
+    send_match_update(ctx.server, &m).await;
+}
+
+/// Handles the player inviting another player to their match.
+pub async fn handle_invite(ctx: &mut PacketContext<'_>, target_id: i32) {
+    let (p_id, m) = match current_match(ctx).await {
+        Some(r) => r,
+        None => return,
+    };
+    let target = match ctx.server.online_players.get(target_id) {
+        Some(t) => t,
+        None => return,
+    };
+
+    let content = {
+        let m = m.read().await;
+        format!("Come join my multiplayer match: [osump://{}/{} {}]", m.id, m.password, m.name)
+    };
+    let sender = ctx.player.read().await.name.clone();
+
+    let t = target.read().await;
+    t.queue.enqueue(builders::match_invite(&sender, &content, &t.name, &p_id)).await;
+}
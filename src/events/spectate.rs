@@ -0,0 +1,153 @@
+use crate::packets::{
+    builders,
+    router::{PacketContext, BanchoServer},
+    rw::SimplePacketQueue,
+    packet::Raw,
+};
+use crate::objects::player::Player;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Handles the player starting to spectate another player.
+pub async fn handle_start_spectating(ctx: &mut PacketContext<'_>, host_id: i32) {
+    let (p_id, current) = {
+        let p = ctx.player.read().await;
+        (p.id, p.spectating)
+    };
+
+    if host_id == p_id || current == Some(host_id) {
+        return;
+    }
+    if current.is_some() {
+        stop_spectating(ctx.server, &ctx.player).await;
+    }
+
+    let host = match ctx.server.online_players.get(host_id) {
+        Some(h) => h,
+        None => return,
+    };
+
+    let others = {
+        let mut h = host.write().await;
+        let others = h.spectators.clone();
+        h.spectators.push(p_id);
+        others
+    };
+    ctx.player.write().await.spectating = Some(host_id);
+
+    host.read().await.queue.enqueue(builders::spectator_joined(&p_id)).await;
+
+    // Introduce the new spectator and the existing ones to each other.
+    let joined = builders::fellow_spectator_joined(&p_id);
+    let mut q = SimplePacketQueue::new();
+    for other in others {
+        ctx.server.online_players.enqueue(other, joined.clone()).await;
+        q.queue(builders::fellow_spectator_joined(&other));
+    }
+    ctx.player.read().await.queue.enqueue(q.into_bytes()).await;
+}
+
+/// Handles the player no longer spectating.
+pub async fn handle_stop_spectating(ctx: &mut PacketContext<'_>) {
+    stop_spectating(ctx.server, &ctx.player).await;
+}
+
+/// # Stop Spectating
+/// Stops the player from spectating whoever they are spectating, notifying
+/// the host and the other spectators.
+pub async fn stop_spectating(srv: &BanchoServer, player: &Arc<RwLock<Player>>) {
+    let (p_id, host_id) = {
+        let mut p = player.write().await;
+        match p.spectating.take() {
+            Some(host_id) => (p.id, host_id),
+            None => return,
+        }
+    };
+
+    let host = match srv.online_players.get(host_id) {
+        Some(h) => h,
+        None => return,
+    };
+
+    let others = {
+        let mut h = host.write().await;
+        h.spectators.retain(|&s| s != p_id);
+        h.spectators.clone()
+    };
+
+    host.read().await.queue.enqueue(builders::spectator_left(&p_id)).await;
+
+    let left = builders::fellow_spectator_left(&p_id);
+    for other in others {
+        srv.online_players.enqueue(other, left.clone()).await;
+    }
+}
+
+/// # Drop Spectators
+/// Stops everyone spectating the player from doing so, for when the player is
+/// going away.
+pub async fn drop_spectators(srv: &BanchoServer, player: &Arc<RwLock<Player>>) {
+    let (p_id, name, spectators) = {
+        let mut p = player.write().await;
+        (p.id, p.name.clone(), std::mem::take(&mut p.spectators))
+    };
+
+    let host_left = builders::notification(&format!("{} is no longer online.", name));
+    for &s_id in spectators.iter() {
+        let s = match srv.online_players.get(s_id) {
+            Some(s) => s,
+            None => continue,
+        };
+        let mut s = s.write().await;
+        if s.spectating != Some(p_id) {
+            continue;
+        }
+        s.spectating = None;
+
+        // The spectators are no longer watching together either.
+        let mut q = SimplePacketQueue::new();
+        for &other in spectators.iter().filter(|&&o| o != s_id) {
+            q.queue(builders::fellow_spectator_left(&other));
+        }
+        q.queue(host_left.clone());
+        s.queue.enqueue(q.into_bytes()).await;
+    }
+}
+
+/// Handles the host sending their replay frames, relaying them to their
+/// spectators.
+pub async fn handle_spectate_frames(ctx: &mut PacketContext<'_>, frames: Raw) {
+    let frames = builders::spectate_frames(&frames.0);
+    let spectators = ctx.player.read().await.spectators.clone();
+
+    for s in spectators {
+        ctx.server.online_players.enqueue(s, frames.clone()).await;
+    }
+}
+
+/// Handles a spectator telling that they do not have the map being played.
+pub async fn handle_cant_spectate(ctx: &mut PacketContext<'_>) {
+    let (p_id, host_id) = {
+        let p = ctx.player.read().await;
+        match p.spectating {
+            Some(host_id) => (p.id, host_id),
+            None => return,
+        }
+    };
+
+    let host = match ctx.server.online_players.get(host_id) {
+        Some(h) => h,
+        None => return,
+    };
+
+    let packet = builders::cant_spectate(&p_id);
+    let spectators = {
+        let h = host.read().await;
+        h.queue.enqueue(packet.clone()).await;
+        h.spectators.clone()
+    };
+
+    for s in spectators {
+        ctx.server.online_players.enqueue(s, packet.clone()).await;
+    }
+}
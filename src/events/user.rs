@@ -0,0 +1,143 @@
+use crate::packets::{
+    builders,
+    router::PacketContext,
+    rw::{SimplePacketQueue, Message},
+    client::ChangeAction,
+};
+use crate::objects::player::Action;
+use crate::consts::modes::{Mode, CustomMode};
+use crate::db::users;
+use crate::logger;
+
+const MOD_RELAX: u32 = 1 << 7;
+const MOD_AUTOPILOT: u32 = 1 << 13;
+
+/// Handles the player changing their in-game action (eg playing a map).
+pub async fn handle_change_action(ctx: &mut PacketContext<'_>, action: ChangeAction) {
+    let mode = Mode::from(action.mode);
+    let mods = action.mods;
+
+    // Relax and autopilot have their own leaderboards (but not on mania).
+    let c_mode = if mode == Mode::MANIA {
+        CustomMode::VANILLA
+    } else if mods & MOD_RELAX > 0 {
+        CustomMode::RELAX
+    } else if mods & MOD_AUTOPILOT > 0 {
+        CustomMode::AUTOPILOT
+    } else {
+        CustomMode::VANILLA
+    };
+
+    let (p_id, mode_changed) = {
+        let mut p = ctx.player.write().await;
+        let mode_changed = p.mode != mode || p.c_mode != c_mode;
+        p.action = Action {
+            id: action.action,
+            text: action.text,
+            bmap_md5: action.map_md5,
+            bmap_id: action.map_id,
+            mods,
+        };
+        p.mode = mode;
+        p.c_mode = c_mode;
+        (p.id, mode_changed)
+    };
+
+    if mode_changed {
+        match users::fetch_stats(&ctx.server.db, p_id, &mode, &c_mode).await {
+            Ok(stats) => ctx.player.write().await.stats = stats,
+            Err(e) => logger::error(format!("Failed to fetch stats for {}: {}", p_id, e)),
+        }
+    }
+
+    let stats = builders::user_stats(&*ctx.player.read().await);
+    ctx.server.online_players.broadcast(stats).await;
+}
+
+/// Handles the player requesting their own stats.
+pub async fn handle_request_status_update(ctx: &mut PacketContext<'_>) {
+    let p = ctx.player.read().await;
+    p.queue.enqueue(builders::user_stats(&p)).await;
+}
+
+/// Handles the player requesting the stats of a list of players.
+pub async fn handle_stats_request(ctx: &mut PacketContext<'_>, ids: Vec<i32>) {
+    let p_id = ctx.player.read().await.id;
+
+    let mut q = SimplePacketQueue::new();
+    for id in ids.into_iter().filter(|&id| id != p_id) {
+        if let Some(other) = ctx.server.online_players.get(id) {
+            q.queue(builders::user_stats(&*other.read().await));
+        }
+    }
+
+    ctx.player.read().await.queue.enqueue(q.into_bytes()).await;
+}
+
+/// Handles the player requesting the presences of a list of players.
+pub async fn handle_presence_request(ctx: &mut PacketContext<'_>, ids: Vec<i32>) {
+    let mut q = SimplePacketQueue::new();
+    for id in ids {
+        if let Some(other) = ctx.server.online_players.get(id) {
+            q.queue(builders::user_presence(&*other.read().await));
+        }
+    }
+
+    ctx.player.read().await.queue.enqueue(q.into_bytes()).await;
+}
+
+/// Handles the player requesting the presences of everyone online.
+pub async fn handle_presence_request_all(ctx: &mut PacketContext<'_>) {
+    let mut q = SimplePacketQueue::new();
+    for other in ctx.server.online_players.all() {
+        q.queue(builders::user_presence(&*other.read().await));
+    }
+
+    ctx.player.read().await.queue.enqueue(q.into_bytes()).await;
+}
+
+/// Handles the player setting (or clearing) their away message.
+pub async fn handle_set_away_message(ctx: &mut PacketContext<'_>, msg: Message) {
+    ctx.player.write().await.away_message = if msg.content.is_empty() {
+        None
+    } else {
+        Some(msg.content)
+    };
+}
+
+/// Handles the player adding another player as a friend.
+pub async fn handle_friend_add(ctx: &mut PacketContext<'_>, friend_id: i32) {
+    let p_id = {
+        let mut p = ctx.player.write().await;
+        if p.friends.contains(&friend_id) {
+            return;
+        }
+        p.friends.push(friend_id);
+        p.id
+    };
+
+    if let Err(e) = users::add_friend(&ctx.server.db, p_id, friend_id).await {
+        logger::error(format!("Failed to add friend for {}: {}", p_id, e));
+    }
+}
+
+/// Handles the player removing another player from their friends.
+pub async fn handle_friend_remove(ctx: &mut PacketContext<'_>, friend_id: i32) {
+    let p_id = {
+        let mut p = ctx.player.write().await;
+        if !p.friends.contains(&friend_id) {
+            return;
+        }
+        p.friends.retain(|&f| f != friend_id);
+        p.id
+    };
+
+    if let Err(e) = users::remove_friend(&ctx.server.db, p_id, friend_id).await {
+        logger::error(format!("Failed to remove friend for {}: {}", p_id, e));
+    }
+}
+
+/// Handles the player toggling whether they accept DMs from non-friends.
+pub async fn handle_toggle_block_dms(ctx: &mut PacketContext<'_>, block: i32) {
+    ctx.player.write().await.allow_dms = block == 0;
+}
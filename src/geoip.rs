@@ -0,0 +1,40 @@
+// GeoIP lookups using a MaxMind GeoLite2 City database.
+use crate::consts::countries;
+use crate::objects::player::Geolocation;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use std::net::IpAddr;
+
+/// # GeoIP
+/// A wrapper around a MaxMind City database reader.
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    /// Opens the MaxMind database at the given path.
+    pub fn open(path: &str) -> Result<Self, MaxMindDBError> {
+        Ok(Self { reader: Reader::open_readfile(path)? })
+    }
+
+    /// # GeoIP Lookup
+    /// Resolves the location of an IP address. Returns `None` if the IP is
+    /// invalid or not present in the database (eg local addresses).
+    pub fn lookup(&self, ip: &str) -> Option<Geolocation> {
+        let addr: IpAddr = ip.parse().ok()?;
+        let city: geoip2::City = self.reader.lookup(addr).ok()?;
+
+        let country = city.country
+            .and_then(|c| c.iso_code)
+            .map(countries::country_id)
+            .unwrap_or(0);
+        let location = city.location
+            .map(|l| (l.latitude.unwrap_or(0.0) as f32, l.longitude.unwrap_or(0.0) as f32))
+            .unwrap_or((0.0, 0.0));
+
+        Some(Geolocation {
+            country,
+            location,
+            ip: ip.to_string(),
+        })
+    }
+}
@@ -0,0 +1,10 @@
+
+pub mod web;
+pub mod logger;
+pub mod consts;
+pub mod objects;
+pub mod packets;
+pub mod config;
+pub mod events;
+pub mod db;
+pub mod geoip;
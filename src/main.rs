@@ -1,19 +1,14 @@
-// Internal module declarations.
-mod web;
-mod logger;
-mod consts;
-mod objects;
-mod packets;
-mod config;
-mod events;
-
-use web::server::{start_server, Address};
-use packets::router::create_bancho_server;
-use config::ensure_config;
+use cu_rs::web::server::{start_server, Address};
+use cu_rs::packets::router::{create_bancho_server, idle_timeout_loop};
+use cu_rs::config::ensure_config;
+use cu_rs::logger;
 
 #[ntex::main]
 async fn main() {
     let conf = ensure_config();
-    create_bancho_server().await;
-    start_server(Address::IPAddress(conf.http_ip)).await;
+    let server = create_bancho_server(conf.clone()).await;
+    tokio::spawn(idle_timeout_loop(server.clone()));
+    if let Err(e) = start_server(Address::IPAddress(conf.http_ip), server).await {
+        logger::error(format!("Server stopped with an error: {}", e));
+    }
 }
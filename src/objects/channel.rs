@@ -0,0 +1,104 @@
+use crate::consts::privileges::Privileges;
+use dashmap::DashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// A structure representing a chat channel.
+pub struct Channel {
+    pub name: String,
+    pub topic: String,
+    /// Whether everyone may read the channel. Otherwise only chat mods may.
+    pub public_read: bool,
+    /// Whether everyone may write to the channel. Otherwise only chat mods
+    /// may.
+    pub public_write: bool,
+    /// Whether the client is told to join the channel as it logs in.
+    pub auto_join: bool,
+    /// Hidden channels are not listed to the client.
+    pub hidden: bool,
+    /// The ids of the players in the channel.
+    pub players: Vec<i32>,
+}
+
+impl Channel {
+    /// Creates an empty channel.
+    pub fn new(name: String, topic: String) -> Self {
+        Self {
+            name,
+            topic,
+            public_read: true,
+            public_write: true,
+            auto_join: false,
+            hidden: false,
+            players: Vec::new(),
+        }
+    }
+
+    /// Checks whether a player with the given privileges may join the channel.
+    pub fn can_read(&self, privileges: &Privileges) -> bool {
+        self.public_read || privileges.chat_mod()
+    }
+
+    /// Checks whether a player with the given privileges may send messages to
+    /// the channel.
+    pub fn can_write(&self, privileges: &Privileges) -> bool {
+        self.public_write || privileges.chat_mod()
+    }
+
+    /// The number of players in the channel, as sent to the client.
+    pub fn player_count(&self) -> i16 {
+        self.players.len() as i16
+    }
+}
+
+/// A list of the chat channels, by name.
+pub struct ChannelList {
+    channels: DashMap<String, Arc<RwLock<Channel>>>,
+}
+
+impl ChannelList {
+    /// Creates an empty channel list.
+    pub fn new() -> Self {
+        Self { channels: DashMap::new() }
+    }
+
+    /// Adds a channel to the list, replacing any with the same name.
+    pub fn add(&self, channel: Channel) {
+        self.channels.insert(channel.name.clone(), Arc::new(RwLock::new(channel)));
+    }
+
+    /// # Channel Get
+    /// Fetches a channel by its name.
+    pub fn get(&self, name: &str) -> Option<Arc<RwLock<Channel>>> {
+        self.channels.get(name).map(|c| c.value().clone())
+    }
+
+    /// # Channel List All
+    /// Returns a snapshot of all channels in the list.
+    pub fn all(&self) -> Vec<Arc<RwLock<Channel>>> {
+        self.channels.iter().map(|c| c.value().clone()).collect()
+    }
+}
+
+impl Default for ChannelList {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permissions() {
+        let mut c = Channel::new("#staff".to_string(), String::new());
+        c.public_read = false;
+        c.public_write = false;
+
+        let player = Privileges::from_bitwise(3);
+        let chat_mod = Privileges::from_bitwise(3 | 2 << 17);
+        assert!(!c.can_read(&player));
+        assert!(!c.can_write(&player));
+        assert!(c.can_read(&chat_mod));
+        assert!(c.can_write(&chat_mod));
+    }
+}
@@ -0,0 +1,173 @@
+use serde::Deserialize;
+use regex::{Regex, NoExpand};
+use tokio::sync::RwLock;
+use std::fmt;
+
+/// # Filter Rule
+/// A single word filter rule, as written in the filter file.
+#[derive(Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+    /// Whether `pattern` is a regex. Otherwise it is matched literally,
+    /// ignoring case.
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(flatten)]
+    pub action: FilterAction,
+}
+
+/// What is done to messages matching a rule.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Replaces every match with `with`.
+    Replace { with: String },
+    /// Stops the message from being sent, showing the sender `warning`.
+    Block { warning: String },
+}
+
+/// The outcome of filtering a message.
+#[derive(Debug, PartialEq)]
+pub enum Filtered {
+    /// The message may be sent, as rewritten by the filter.
+    Allow(String),
+    /// The message may not be sent. Holds the warning for the sender.
+    Block(String),
+}
+
+/// An error loading the word filter rules.
+#[derive(Debug)]
+pub enum FilterError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Regex(regex::Error),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read the filter file: {}", e),
+            Self::Json(e) => write!(f, "invalid filter file: {}", e),
+            Self::Regex(e) => write!(f, "invalid filter pattern: {}", e),
+        }
+    }
+}
+
+impl From<std::io::Error> for FilterError {
+    fn from(e: std::io::Error) -> Self { Self::Io(e) }
+}
+
+impl From<serde_json::Error> for FilterError {
+    fn from(e: serde_json::Error) -> Self { Self::Json(e) }
+}
+
+impl From<regex::Error> for FilterError {
+    fn from(e: regex::Error) -> Self { Self::Regex(e) }
+}
+
+/// # Word Filter
+/// Rewrites or blocks chat messages according to a list of rules, applied in
+/// order. The rules may be reloaded while the server is running.
+pub struct WordFilter {
+    rules: RwLock<Vec<(Regex, FilterAction)>>,
+}
+
+impl WordFilter {
+    /// Creates a word filter with no rules, letting every message through.
+    pub fn new() -> Self {
+        Self { rules: RwLock::new(Vec::new()) }
+    }
+
+    /// # Set Rules
+    /// Replaces the filter's rules, returning how many there now are. The old
+    /// rules are kept if any of the new ones are invalid.
+    pub async fn set_rules(&self, rules: Vec<FilterRule>) -> Result<usize, FilterError> {
+        let compiled = rules.into_iter()
+            .map(|r| {
+                let pattern = if r.regex {
+                    r.pattern
+                } else {
+                    format!("(?i){}", regex::escape(&r.pattern))
+                };
+                Ok((Regex::new(&pattern)?, r.action))
+            })
+            .collect::<Result<Vec<_>, FilterError>>()?;
+
+        let count = compiled.len();
+        *self.rules.write().await = compiled;
+        Ok(count)
+    }
+
+    /// # Load Rules
+    /// Replaces the filter's rules with those in the JSON file at `path`,
+    /// returning how many there now are.
+    pub async fn load(&self, path: &str) -> Result<usize, FilterError> {
+        let contents = tokio::fs::read_to_string(path).await?;
+        self.set_rules(serde_json::from_str(&contents)?).await
+    }
+
+    /// # Apply Filter
+    /// Runs a message through the filter.
+    pub async fn apply(&self, msg: &str) -> Filtered {
+        let mut msg = msg.to_string();
+
+        for (regex, action) in self.rules.read().await.iter() {
+            match action {
+                FilterAction::Replace { with } => {
+                    msg = regex.replace_all(&msg, NoExpand(with)).into_owned();
+                },
+                FilterAction::Block { warning } => {
+                    if regex.is_match(&msg) {
+                        return Filtered::Block(warning.clone());
+                    }
+                },
+            }
+        }
+
+        Filtered::Allow(msg)
+    }
+}
+
+impl Default for WordFilter {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: &str) -> Vec<FilterRule> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[tokio::test]
+    async fn literal_replace_ignores_case() {
+        let filter = WordFilter::new();
+        filter.set_rules(rules(r#"[{"pattern": "bad.word", "action": "replace", "with": "***"}]"#)).await.unwrap();
+
+        assert_eq!(filter.apply("a BAD.word here").await, Filtered::Allow("a *** here".to_string()));
+        // The dot is literal.
+        assert_eq!(filter.apply("badxword").await, Filtered::Allow("badxword".to_string()));
+    }
+
+    #[tokio::test]
+    async fn regex_block() {
+        let filter = WordFilter::new();
+        filter.set_rules(rules(
+            r#"[{"pattern": "discord\\.gg/\\w+", "regex": true, "action": "block", "warning": "No invites."}]"#
+        )).await.unwrap();
+
+        assert_eq!(filter.apply("join discord.gg/abc").await, Filtered::Block("No invites.".to_string()));
+        assert_eq!(filter.apply("hello").await, Filtered::Allow("hello".to_string()));
+    }
+
+    #[tokio::test]
+    async fn invalid_rules_keep_old_ones() {
+        let filter = WordFilter::new();
+        filter.set_rules(rules(r#"[{"pattern": "a", "action": "replace", "with": "b"}]"#)).await.unwrap();
+
+        let bad = rules(r#"[{"pattern": "(", "regex": true, "action": "replace", "with": ""}]"#);
+        assert!(filter.set_rules(bad).await.is_err());
+        assert_eq!(filter.apply("a").await, Filtered::Allow("b".to_string()));
+    }
+}
@@ -1 +1,5 @@
 pub mod player;
+pub mod version;
+pub mod multiplayer;
+pub mod channel;
+pub mod filter;
@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+};
+use tokio::sync::{RwLock, Mutex};
+
+/// The amount of slots in a multiplayer match.
+pub const MAX_SLOTS: usize = 16;
+
+// Slot statuses, as used by the client.
+pub const SLOT_OPEN: u8 = 1;
+pub const SLOT_LOCKED: u8 = 2;
+pub const SLOT_NOT_READY: u8 = 4;
+pub const SLOT_READY: u8 = 8;
+pub const SLOT_NO_MAP: u8 = 16;
+pub const SLOT_PLAYING: u8 = 32;
+pub const SLOT_COMPLETE: u8 = 64;
+pub const SLOT_QUIT: u8 = 128;
+/// Any of the statuses that mean a player is in the slot.
+pub const SLOT_HAS_PLAYER: u8 = SLOT_NOT_READY | SLOT_READY | SLOT_NO_MAP | SLOT_PLAYING | SLOT_COMPLETE;
+
+// Match teams.
+pub const TEAM_NEUTRAL: u8 = 0;
+pub const TEAM_BLUE: u8 = 1;
+pub const TEAM_RED: u8 = 2;
+
+// Match team types.
+pub const TEAM_TYPE_TEAM_VS: u8 = 2;
+pub const TEAM_TYPE_TAG_TEAM_VS: u8 = 3;
+
+/// Mods that affect the speed of the map, which are always set match-wide.
+pub const SPEED_MODS: u32 = 64 | 256 | 512; // DT | HT | NC
+
+/// A structure representing a single slot in a multiplayer match.
+#[derive(Clone, Copy)]
+pub struct Slot {
+    pub status: u8,
+    pub team: u8,
+    pub player: Option<i32>,
+    pub mods: u32,
+    pub loaded: bool,
+    pub skipped: bool,
+}
+
+impl Slot {
+    /// Creates an empty, open slot.
+    pub fn new() -> Self {
+        Self {
+            status: SLOT_OPEN,
+            team: TEAM_NEUTRAL,
+            player: None,
+            mods: 0,
+            loaded: false,
+            skipped: false,
+        }
+    }
+
+    /// Checks if a player is occupying the slot.
+    #[inline(always)]
+    pub fn has_player(&self) -> bool {
+        self.status & SLOT_HAS_PLAYER > 0
+    }
+
+    /// Empties the slot, leaving it open.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Default for Slot {
+    fn default() -> Self { Self::new() }
+}
+
+/// The settings of a match, as sent by the client when creating or changing
+/// a match.
+pub struct MatchSettings {
+    pub name: String,
+    pub password: String,
+    pub mods: u32,
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+    pub host: i32,
+    pub mode: u8,
+    pub win_condition: u8,
+    pub team_type: u8,
+    pub freemods: bool,
+    pub seed: i32,
+}
+
+/// A structure representing a multiplayer match.
+pub struct Match {
+    pub id: i32,
+    pub name: String,
+    pub password: String,
+    pub in_progress: bool,
+    pub mods: u32,
+
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+
+    pub slots: [Slot; MAX_SLOTS],
+    pub host: i32,
+    pub mode: u8,
+    pub win_condition: u8,
+    pub team_type: u8,
+    pub freemods: bool,
+    pub seed: i32,
+}
+
+impl Match {
+    /// Creates a new match from the settings sent by its creator.
+    pub fn new(id: i32, settings: MatchSettings) -> Self {
+        Self {
+            id,
+            name: settings.name,
+            password: settings.password,
+            in_progress: false,
+            mods: settings.mods,
+            map_name: settings.map_name,
+            map_id: settings.map_id,
+            map_md5: settings.map_md5,
+            slots: [Slot::new(); MAX_SLOTS],
+            host: settings.host,
+            mode: settings.mode,
+            win_condition: settings.win_condition,
+            team_type: settings.team_type,
+            freemods: settings.freemods,
+            seed: settings.seed,
+        }
+    }
+
+    /// Fetches the index of the slot occupied by the given player.
+    pub fn slot_of(&self, p_id: i32) -> Option<usize> {
+        self.slots.iter().position(|s| s.player == Some(p_id))
+    }
+
+    /// Fetches the index of the first open slot.
+    pub fn free_slot(&self) -> Option<usize> {
+        self.slots.iter().position(|s| s.status == SLOT_OPEN)
+    }
+
+    /// Returns the ids of all players in the match.
+    pub fn players(&self) -> Vec<i32> {
+        self.slots.iter().filter_map(|s| s.player).collect()
+    }
+
+    /// Returns the ids of all players in slots with the given status.
+    pub fn players_with_status(&self, status: u8) -> Vec<i32> {
+        self.slots.iter()
+            .filter(|s| s.status & status > 0)
+            .filter_map(|s| s.player)
+            .collect()
+    }
+
+    /// Checks if anyone is playing and every playing slot matches `f`.
+    pub fn all_playing(&self, f: impl Fn(&Slot) -> bool) -> bool {
+        let mut playing = self.slots.iter().filter(|s| s.status == SLOT_PLAYING).peekable();
+        playing.peek().is_some() && playing.all(f)
+    }
+
+    /// Places a player into a slot, assigning them a team if the match is
+    /// played in teams.
+    pub fn place_player(&mut self, slot_id: usize, p_id: i32) {
+        let team = if self.is_team_match() { TEAM_RED } else { TEAM_NEUTRAL };
+        let slot = &mut self.slots[slot_id];
+        slot.status = SLOT_NOT_READY;
+        slot.team = team;
+        slot.player = Some(p_id);
+    }
+
+    /// Checks if the match is played in teams.
+    #[inline(always)]
+    pub fn is_team_match(&self) -> bool {
+        self.team_type == TEAM_TYPE_TEAM_VS || self.team_type == TEAM_TYPE_TAG_TEAM_VS
+    }
+
+    /// Sets every ready player back to not ready (eg after a map change).
+    pub fn unready_players(&mut self) {
+        for slot in self.slots.iter_mut() {
+            if slot.status == SLOT_READY {
+                slot.status = SLOT_NOT_READY;
+            }
+        }
+    }
+}
+
+/// A list of all active multiplayer matches.
+pub struct MatchList {
+    matches: Mutex<HashMap<i32, Arc<RwLock<Match>>>>,
+}
+
+impl MatchList {
+    /// Creates an empty match list.
+    pub fn new() -> Self {
+        Self { matches: Mutex::new(HashMap::new()) }
+    }
+
+    /// # Match Create
+    /// Creates a match under the lowest free id and adds it to the list.
+    /// Returns `None` if every id is taken.
+    pub async fn create(&self, settings: MatchSettings) -> Option<Arc<RwLock<Match>>> {
+        let mut matches = self.matches.lock().await;
+        // The client stores match ids in an i16.
+        let id = (1..=i16::MAX as i32).find(|id| !matches.contains_key(id))?;
+
+        let m = Arc::from(RwLock::from(Match::new(id, settings)));
+        matches.insert(id, m.clone());
+        Some(m)
+    }
+
+    /// # Match Get
+    /// Fetches a match by its id.
+    pub async fn get(&self, id: i32) -> Option<Arc<RwLock<Match>>> {
+        self.matches.lock().await.get(&id).cloned()
+    }
+
+    /// # Match List All
+    /// Returns a snapshot of all matches currently in the list.
+    pub async fn all(&self) -> Vec<Arc<RwLock<Match>>> {
+        self.matches.lock().await.values().cloned().collect()
+    }
+
+    /// # Match Remove
+    /// Removes a match from the list if found, else does nothing.
+    pub async fn remove(&self, id: i32) {
+        self.matches.lock().await.remove(&id);
+    }
+}
+
+impl Default for MatchList {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> MatchSettings {
+        MatchSettings {
+            name: "match".to_string(),
+            password: String::new(),
+            mods: 0,
+            map_name: String::new(),
+            map_id: 0,
+            map_md5: String::new(),
+            host: 3,
+            mode: 0,
+            win_condition: 0,
+            team_type: 0,
+            freemods: false,
+            seed: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn create_uses_lowest_free_id() {
+        let matches = MatchList::new();
+        let first = matches.create(settings()).await.unwrap();
+        let second = matches.create(settings()).await.unwrap();
+        assert_eq!(first.read().await.id, 1);
+        assert_eq!(second.read().await.id, 2);
+
+        matches.remove(1).await;
+        assert_eq!(matches.create(settings()).await.unwrap().read().await.id, 1);
+    }
+}
@@ -2,20 +2,31 @@ use crate::consts::{
     privileges::Privileges,
     modes::{Mode, CustomMode},
 };
-use std::{
-    collections::HashMap,
-    sync::{Arc}
-};
+use std::sync::Arc;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use dashmap::{DashMap, mapref::entry::Entry};
 use tokio::sync::{RwLock, Mutex};
 
 
 /// A structure representing a physical location of a user.
 pub struct Geolocation {
     pub country: u8,
-    pub location: (f32, f32),
+    pub location: (f32, f32), // (latitude, longitude)
     pub ip: String,
 }
 
+impl Geolocation {
+    /// Creates a `Geolocation` for an IP with no known location.
+    pub fn unknown(ip: &str) -> Self {
+        Self {
+            country: 0,
+            location: (0.0, 0.0),
+            ip: ip.to_string(),
+        }
+    }
+}
+
 const BYTEQUEUE_CAPACITY: usize = 512;
 
 /// A thread-safe, async-friendly queue of bytes.
@@ -36,9 +47,7 @@ impl ByteQueue {
     /// clearing.
     pub async fn empty(&self) -> Vec<u8> {
         let mut q = self.queue.lock().await;
-        let old_contents = q.clone();
-        q.clear();
-        old_contents
+        std::mem::take(&mut *q)
     }
 
     /// Enqueues bytes to the `ByteQueue`.
@@ -48,15 +57,104 @@ impl ByteQueue {
     }
 }
 
+impl Default for ByteQueue {
+    fn default() -> Self { Self::new() }
+}
+
 /// A structure representing a Player's in-game status.
 pub struct Action {
     pub id: u8,
     pub text: String,
     pub bmap_md5: String,
-    pub bmap_id: String,
+    pub bmap_id: i32,
     pub mods: u32, // TODO: Mods class.
 }
 
+impl Action {
+    /// Creates an instance of `Action` representing an idle player.
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            text: String::new(),
+            bmap_md5: String::new(),
+            bmap_id: 0,
+            mods: 0,
+        }
+    }
+}
+
+impl Default for Action {
+    fn default() -> Self { Self::new() }
+}
+
+/// A structure representing a Player's statistics in a single mode.
+pub struct Stats {
+    pub ranked_score: i64,
+    pub total_score: i64,
+    pub accuracy: f32,
+    pub playcount: i32,
+    pub rank: i32,
+    pub pp: i32,
+}
+
+impl Stats {
+    /// Creates an instance of `Stats` for a player that has never played.
+    pub fn new() -> Self {
+        Self {
+            ranked_score: 0,
+            total_score: 0,
+            accuracy: 0.0,
+            playcount: 0,
+            rank: 0,
+            pp: 0,
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self { Self::new() }
+}
+
+/// A sliding window of when a player sent their recent messages, used to
+/// catch spam.
+pub struct MessageRate {
+    sent: VecDeque<Instant>,
+    /// The number of times the player was caught spamming this session.
+    pub offences: usize,
+}
+
+impl MessageRate {
+    /// Creates an instance of `MessageRate` for a player yet to send anything.
+    pub fn new() -> Self {
+        Self {
+            sent: VecDeque::new(),
+            offences: 0,
+        }
+    }
+
+    /// # Record Message
+    /// Records a message sent at `now`, returning whether more than `limit`
+    /// messages have been sent within `window`. The window is cleared when
+    /// the limit is exceeded, so each offence is only counted once.
+    pub fn record(&mut self, now: Instant, limit: usize, window: Duration) -> bool {
+        while matches!(self.sent.front(), Some(&t) if now.duration_since(t) >= window) {
+            self.sent.pop_front();
+        }
+        self.sent.push_back(now);
+
+        if self.sent.len() <= limit {
+            return false;
+        }
+        self.sent.clear();
+        self.offences += 1;
+        true
+    }
+}
+
+impl Default for MessageRate {
+    fn default() -> Self { Self::new() }
+}
+
 /// A structure representing an in-game player.
 pub struct Player {
     pub id: i32,
@@ -69,34 +167,149 @@ pub struct Player {
     pub action: Action,
     pub mode: Mode,
     pub c_mode: CustomMode,
+    pub stats: Stats,
+    pub friends: Vec<i32>,
+    pub utc_offset: i8,
+    pub allow_dms: bool,
+    pub away_message: Option<String>,
+    /// Whether this is a session of the tournament client, which may share
+    /// its account with other sessions.
+    pub tourney: bool,
+
+    pub spectating: Option<i32>,
+    pub spectators: Vec<i32>,
+    pub match_id: Option<i32>,
+    /// The names of the channels the player is in.
+    pub channels: Vec<String>,
 
+    /// The unix timestamp the player's silence ends at. In the past if they
+    /// are not silenced.
+    pub silence_end: i64,
+    pub message_rate: MessageRate,
+
+    /// When the player logged in.
+    pub login_time: Instant,
+    /// When the player's client last made a request.
+    pub last_seen: Instant,
     pub queue: ByteQueue,
 }
 
+impl Player {
+    /// # Silenced
+    /// Checks whether the player is currently silenced.
+    pub fn silenced(&self) -> bool {
+        self.silence_end > unix_time()
+    }
+
+    /// # Silence Remaining
+    /// Returns the number of seconds left in the player's silence, 0 if they
+    /// are not silenced.
+    pub fn silence_remaining(&self) -> i32 {
+        (self.silence_end - unix_time()).max(0) as i32
+    }
+}
+
+/// # Unix Time
+/// Returns the current unix timestamp, in seconds.
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// # Safe Name
+/// Converts a username into its Ripple "safe" form, used for lookups.
+pub fn make_safe(name: &str) -> String {
+    name.trim().to_lowercase().replace(" ", "_")
+}
+
+/// A player held by a `PlayerList`, alongside the keys it is indexed by.
+struct PlayerEntry {
+    player: Arc<RwLock<Player>>,
+    token: String,
+    safe_name: String,
+}
+
 /// A list of players, holding Arc + RwLock references and supporting
 /// broadcasting efficiently.
+///
+/// The players are held in a sharded concurrent map, so lookups never wait
+/// on each other, and broadcasts iterate over a snapshot rather than holding
+/// the map while awaiting each player.
+///
+/// Players are indexed by id, token and safe name. The indices are only ever
+/// changed while holding the player's entry in the id map, and lookups by
+/// token or name are checked against that entry, so they never return a
+/// player that no longer has the key.
+///
+/// The tournament client opens several sessions on the same account. The
+/// first one is held like any other player, while the rest are only held by
+/// their token, and take over the id once the first one is removed.
 pub struct PlayerList {
-    players: Mutex<HashMap<i32, Arc<RwLock<Player>>>>,
+    players: DashMap<i32, PlayerEntry>,
+    tokens: DashMap<String, i32>,
+    names: DashMap<String, i32>,
+    tourney: DashMap<String, (i32, Arc<RwLock<Player>>)>,
 }
 
 impl PlayerList {
     /// Creates an empty player list.
     pub fn new() -> Self {
-        Self { players: Mutex::new(HashMap::new()) }
+        Self {
+            players: DashMap::new(),
+            tokens: DashMap::new(),
+            names: DashMap::new(),
+            tourney: DashMap::new(),
+        }
     }
 
     /// Adds a player from a directly owner player structure.
-    pub async fn add_player(&self, p: Player) {
-        let p_id = p.id.clone();
+    pub fn add_player(&self, p: Player) {
+        let (p_id, token, safe_name, tourney) = (p.id, p.uuid.clone(), p.safe_name.clone(), p.tourney);
         let pl = Arc::from(RwLock::from(p));
 
-        self.players.lock().await.insert(p_id, pl);
+        self.insert(p_id, token, safe_name, tourney, pl);
+    }
+
+    /// Adds an already shared player to the list.
+    pub async fn add(&self, p: Arc<RwLock<Player>>) {
+        let (p_id, token, safe_name, tourney) = {
+            let pl = p.read().await;
+            (pl.id, pl.uuid.clone(), pl.safe_name.clone(), pl.tourney)
+        };
+
+        self.insert(p_id, token, safe_name, tourney, p);
+    }
+
+    /// Inserts a player under all of its keys, replacing any player already
+    /// held with the same id. Tournament client sessions are instead held by
+    /// their token alone if their account already has a session.
+    fn insert(&self, p_id: i32, token: String, safe_name: String, tourney: bool, player: Arc<RwLock<Player>>) {
+        let entry = self.players.entry(p_id);
+        if tourney && matches!(entry, Entry::Occupied(_)) {
+            self.tourney.insert(token, (p_id, player));
+            return;
+        }
+
+        let mut entry = entry.or_insert_with(|| PlayerEntry {
+            player: player.clone(),
+            token: String::new(),
+            safe_name: String::new(),
+        });
+
+        self.tokens.remove_if(&entry.token, |_, id| *id == p_id);
+        self.names.remove_if(&entry.safe_name, |_, id| *id == p_id);
+        self.tokens.insert(token.clone(), p_id);
+        self.names.insert(safe_name.clone(), p_id);
+
+        *entry = PlayerEntry { player, token, safe_name };
     }
 
     /// # Broadcast
     /// Queues the given packet vector to all players in the list.
     pub async fn broadcast(&self, packet: Vec<u8>) {
-        for player in self.players.lock().await.values() {
+        for player in self.all() {
             let p = player.read().await;
 
             p.queue.enqueue(packet.clone()).await;
@@ -106,16 +319,283 @@ impl PlayerList {
     /// # Player Get
     /// Fetches a copy of the arc + rwlocked player object if found, else 
     /// returns `None`.
-    pub async fn get(&self, p_id: i32) -> Option<Arc<RwLock<Player>>> {
-        match self.players.lock().await.get(&p_id) {
-            Some(pl) => { Some(pl.clone()) },
-            _ => None,
+    pub fn get(&self, p_id: i32) -> Option<Arc<RwLock<Player>>> {
+        self.players.get(&p_id).map(|e| e.player.clone())
+    }
+
+    /// # Player Get By Token
+    /// Fetches the player owning the given session token, if any.
+    pub fn get_by_token(&self, token: &str) -> Option<Arc<RwLock<Player>>> {
+        if let Some(p) = self.tourney.get(token) {
+            return Some(p.1.clone());
+        }
+
+        let p_id = *self.tokens.get(token)?;
+        let entry = self.players.get(&p_id)?;
+
+        if entry.token == token { Some(entry.player.clone()) } else { None }
+    }
+
+    /// # Player Get By Name
+    /// Fetches a player by their username, in any form that converts to their
+    /// safe name.
+    pub fn get_by_name(&self, name: &str) -> Option<Arc<RwLock<Player>>> {
+        let safe_name = make_safe(name);
+        let p_id = *self.names.get(&safe_name)?;
+        let entry = self.players.get(&p_id)?;
+
+        if entry.safe_name == safe_name { Some(entry.player.clone()) } else { None }
+    }
+
+    /// # Player List All
+    /// Returns a snapshot of all players currently in the list.
+    pub fn all(&self) -> Vec<Arc<RwLock<Player>>> {
+        self.players.iter()
+            .map(|e| e.player.clone())
+            .chain(self.tourney.iter().map(|e| e.1.clone()))
+            .collect()
+    }
+
+    /// # Player Sessions
+    /// Returns a snapshot of all sessions of the given account.
+    pub fn sessions(&self, p_id: i32) -> Vec<Arc<RwLock<Player>>> {
+        self.get(p_id).into_iter()
+            .chain(self.tourney.iter().filter(|e| e.0 == p_id).map(|e| e.1.clone()))
+            .collect()
+    }
+
+    /// # Player Enqueue
+    /// Queues the given packet vector to a single player in the list, if
+    /// present.
+    pub async fn enqueue(&self, p_id: i32, packet: Vec<u8>) {
+        if let Some(p) = self.get(p_id) {
+            p.read().await.queue.enqueue(packet).await;
         }
     }
 
     /// # Player Remove
-    /// Removes a player from the list if found, else does nothing.
-    pub async fn remove(&mut self, p_id: i32) {
-        self.players.lock().await.remove(&p_id);
+    /// Removes all sessions of a player and all of their keys from the list
+    /// if found, else does nothing.
+    pub fn remove(&self, p_id: i32) {
+        if let Entry::Occupied(entry) = self.players.entry(p_id) {
+            self.tokens.remove_if(&entry.get().token, |_, id| *id == p_id);
+            self.names.remove_if(&entry.get().safe_name, |_, id| *id == p_id);
+            self.tourney.retain(|_, (id, _)| *id != p_id);
+            entry.remove();
+        }
+    }
+
+    /// # Player Remove Session
+    /// Removes the given player and all of their keys from the list, unless
+    /// their id has since been taken by another session. If the account has
+    /// other tournament client sessions, one of them takes over the id.
+    /// Returns whether the player was removed.
+    pub fn remove_session(&self, p_id: i32, player: &Arc<RwLock<Player>>) -> bool {
+        match self.players.entry(p_id) {
+            Entry::Occupied(mut entry) if Arc::ptr_eq(&entry.get().player, player) => {
+                self.tokens.remove_if(&entry.get().token, |_, id| *id == p_id);
+
+                let next = self.tourney_token(|(id, _)| *id == p_id)
+                    .and_then(|token| self.tourney.remove(&token));
+                match next {
+                    Some((token, (_, next))) => {
+                        self.tokens.insert(token.clone(), p_id);
+                        let e = entry.get_mut();
+                        e.player = next;
+                        e.token = token;
+                    },
+                    None => {
+                        self.names.remove_if(&entry.get().safe_name, |_, id| *id == p_id);
+                        entry.remove();
+                    },
+                }
+                true
+            },
+            _ => match self.tourney_token(|(_, p)| Arc::ptr_eq(p, player)) {
+                Some(token) => self.tourney.remove(&token).is_some(),
+                None => false,
+            },
+        }
+    }
+
+    /// Finds the token of a tournament client session matching `f`.
+    fn tourney_token(&self, f: impl Fn(&(i32, Arc<RwLock<Player>>)) -> bool) -> Option<String> {
+        self.tourney.iter().find(|e| f(e.value())).map(|e| e.key().clone())
+    }
+
+    /// # Player Count
+    /// Returns the number of players currently in the list.
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Returns whether the list holds no players.
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+}
+
+impl Default for PlayerList {
+    fn default() -> Self { Self::new() }
+}
+
+/// Creates an idle player with no stats or privileges, for tests.
+#[cfg(test)]
+pub(crate) fn test_player(id: i32, name: &str, token: &str) -> Player {
+    Player {
+        id,
+        name: name.to_string(),
+        safe_name: make_safe(name),
+        uuid: token.to_string(),
+        location: Geolocation::unknown("127.0.0.1"),
+        privileges: Privileges::new(),
+        action: Action::new(),
+        mode: Mode::STANDARD,
+        c_mode: CustomMode::VANILLA,
+        stats: Stats::new(),
+        friends: Vec::new(),
+        utc_offset: 0,
+        allow_dms: true,
+        away_message: None,
+        tourney: false,
+        spectating: None,
+        spectators: Vec::new(),
+        match_id: None,
+        channels: Vec::new(),
+        silence_end: 0,
+        message_rate: MessageRate::new(),
+        login_time: Instant::now(),
+        last_seen: Instant::now(),
+        queue: ByteQueue::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups() {
+        let list = PlayerList::new();
+        list.add_player(test_player(3, "Some Player", "token"));
+
+        assert!(list.get(3).is_some());
+        assert!(list.get_by_token("token").is_some());
+        assert!(list.get_by_name("some_player").is_some());
+        assert!(list.get_by_name("Some Player").is_some());
+        assert!(list.get_by_token("other").is_none());
+        assert!(list.get_by_name("other").is_none());
+    }
+
+    #[test]
+    fn remove_drops_all_keys() {
+        let list = PlayerList::new();
+        list.add_player(test_player(3, "bob", "token"));
+        list.remove(3);
+
+        assert!(list.get(3).is_none());
+        assert!(list.get_by_token("token").is_none());
+        assert!(list.get_by_name("bob").is_none());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn replacing_a_player_invalidates_old_keys() {
+        let list = PlayerList::new();
+        list.add_player(test_player(3, "bob", "old"));
+        list.add_player(test_player(3, "bobby", "new"));
+
+        assert!(list.get_by_token("old").is_none());
+        assert!(list.get_by_name("bob").is_none());
+        assert!(list.get_by_token("new").is_some());
+        assert!(list.get_by_name("bobby").is_some());
+        assert_eq!(list.len(), 1);
+    }
+
+    #[test]
+    fn remove_session_keeps_newer_session() {
+        let list = PlayerList::new();
+        let old = Arc::new(RwLock::new(test_player(3, "bob", "old")));
+        list.insert(3, "old".to_string(), "bob".to_string(), false, old.clone());
+        list.add_player(test_player(3, "bob", "new"));
+
+        assert!(!list.remove_session(3, &old));
+        assert!(list.get_by_token("new").is_some());
+
+        let new = list.get(3).unwrap();
+        assert!(list.remove_session(3, &new));
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn tourney_sessions_share_an_account() {
+        let list = PlayerList::new();
+        let mut first = test_player(3, "bob", "first");
+        first.tourney = true;
+        list.add_player(first);
+        let mut second = test_player(3, "bob", "second");
+        second.tourney = true;
+        list.add_player(second);
+
+        assert!(list.get_by_token("first").is_some());
+        assert!(list.get_by_token("second").is_some());
+        assert_eq!(list.sessions(3).len(), 2);
+        assert_eq!(list.all().len(), 2);
+        assert_eq!(list.len(), 1);
+
+        // The second session takes over once the first is gone.
+        let first = list.get(3).unwrap();
+        assert!(list.remove_session(3, &first));
+        assert!(list.get_by_token("first").is_none());
+        let second = list.get_by_token("second").unwrap();
+        assert!(Arc::ptr_eq(&list.get(3).unwrap(), &second));
+        assert!(list.get_by_name("bob").is_some());
+
+        assert!(list.remove_session(3, &second));
+        assert!(list.is_empty());
+        assert!(list.get_by_token("second").is_none());
+    }
+
+    #[test]
+    fn silences() {
+        let mut p = test_player(3, "bob", "token");
+        assert!(!p.silenced());
+        assert_eq!(p.silence_remaining(), 0);
+
+        p.silence_end = unix_time() + 60;
+        assert!(p.silenced());
+        assert!(p.silence_remaining() > 0 && p.silence_remaining() <= 60);
+
+        p.silence_end = unix_time() - 60;
+        assert!(!p.silenced());
+        assert_eq!(p.silence_remaining(), 0);
+    }
+
+    #[test]
+    fn message_rate() {
+        let mut rate = MessageRate::new();
+        let window = Duration::from_secs(10);
+        let start = Instant::now();
+
+        for i in 0..3 {
+            assert!(!rate.record(start + Duration::from_secs(i), 3, window));
+        }
+        assert!(rate.record(start + Duration::from_secs(3), 3, window));
+        assert_eq!(rate.offences, 1);
+
+        // Old messages fall out of the window.
+        for i in 0..3 {
+            assert!(!rate.record(start + Duration::from_secs(4 + i * 5), 3, window));
+        }
+        assert_eq!(rate.offences, 1);
+    }
+
+    #[tokio::test]
+    async fn add_shared_player() {
+        let list = PlayerList::new();
+        list.add(Arc::new(RwLock::new(test_player(3, "bob", "token")))).await;
+
+        assert!(list.get_by_token("token").is_some());
+        assert!(list.get_by_name("bob").is_some());
     }
 }
@@ -0,0 +1,59 @@
+/// A structure representing a parsed osu! client version string, such as
+/// `b20210125.2cuttingedge`.
+pub struct ClientVersion {
+    pub date: u32,
+    pub revision: u32,
+    pub stream: String,
+}
+
+impl ClientVersion {
+    /// Attempts to parse an osu! version string. On fail, returns `None`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.strip_prefix('b')?;
+        let date: u32 = version.get(..8)?.parse().ok()?;
+
+        let mut rest = &version[8..];
+        let mut revision = 0;
+        if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+            revision = r[..end].parse().ok()?;
+            rest = &r[end..];
+        }
+
+        // Stable builds carry no stream suffix.
+        let stream = if rest.is_empty() { "stable" } else { rest };
+
+        Some(Self {
+            date,
+            revision,
+            stream: stream.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(version: &str) -> (u32, u32, String) {
+        let v = ClientVersion::parse(version).unwrap();
+        (v.date, v.revision, v.stream)
+    }
+
+    #[test]
+    fn parses_streams() {
+        assert_eq!(parse("b20211231"), (20211231, 0, "stable".to_string()));
+        assert_eq!(parse("b20211231.2"), (20211231, 2, "stable".to_string()));
+        assert_eq!(parse("b20210125.2beta"), (20210125, 2, "beta".to_string()));
+        assert_eq!(parse("b20210125cuttingedge"), (20210125, 0, "cuttingedge".to_string()));
+        assert_eq!(parse("b20210125.13cuttingedge"), (20210125, 13, "cuttingedge".to_string()));
+        assert_eq!(parse("b20211231tourney"), (20211231, 0, "tourney".to_string()));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for version in ["", "b", "20211231", "b2021123", "b2021x231", "b20211231.beta", "b20211231.x2"] {
+            assert!(ClientVersion::parse(version).is_none(), "{} parsed", version);
+        }
+    }
+}
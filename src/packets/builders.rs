@@ -1,20 +1,645 @@
 // cu.rs packet builders.
-use crate::packets::rw::Writer;
-use crate::consts::packet_ids;
+use crate::packets::server::{
+    ServerPacket, UserPresence, UserStats, UserLogout, ChannelInfo, BeatmapInfo, BeatmapInfoReply,
+};
+use crate::packets::packet::{Raw, MatchData};
+use crate::packets::rw::Message;
+use crate::objects::player::Player;
+use crate::objects::multiplayer::{Match, MAX_SLOTS};
+
+/// The bancho protocol version implemented by cu.rs.
+pub const PROTOCOL_VERSION: i32 = 19;
 
 /// Writes a Server Restart packet.
-pub fn server_restart(time: &u32) -> Vec<u8> {
-    let mut w = Writer::new(packet_ids::SRV_RESTART);
-    w.write_int(time);
-    w.build()
+pub fn server_restart(time: &i32) -> Vec<u8> {
+    ServerPacket::Restart(*time).encode()
 }
 
 pub fn ping() -> Vec<u8> {
-    Writer::new(packet_ids::SRV_PONG).build()
+    ServerPacket::Pong.encode()
 }
 
 pub fn login_reply(user_id: &i32) -> Vec<u8> {
-    let mut w = Writer::new(packet_ids::SRV_USER_ID);
-    w.write_int(user_id);
-    w.build()
+    ServerPacket::UserId(*user_id).encode()
+}
+
+/// Writes the bancho protocol version packet.
+pub fn protocol_version(version: &i32) -> Vec<u8> {
+    ServerPacket::ProtocolVersion(*version).encode()
+}
+
+/// Writes the client privileges packet (`BanchoPriv` flags).
+pub fn bancho_privileges(privs: &i32) -> Vec<u8> {
+    ServerPacket::Privileges(*privs).encode()
+}
+
+/// Writes the friends list of a player.
+pub fn friends_list(friends: &[i32]) -> Vec<u8> {
+    ServerPacket::FriendsList(friends.to_vec()).encode()
+}
+
+/// Writes the packet marking the end of the channel listing.
+pub fn channel_info_end() -> Vec<u8> {
+    ServerPacket::ChannelInfoEnd.encode()
+}
+
+/// Writes a notification to be shown to the user.
+pub fn notification(msg: &str) -> Vec<u8> {
+    ServerPacket::Notification(msg.to_string()).encode()
+}
+
+/// Writes the packet forcing the client to update before logging in.
+pub fn version_update_forced() -> Vec<u8> {
+    ServerPacket::VersionUpdateForced.encode()
+}
+
+/// Writes the packet telling the client its account is restricted.
+pub fn account_restricted() -> Vec<u8> {
+    ServerPacket::AccountRestricted.encode()
+}
+
+/// Writes the packet telling the client there is an update available.
+pub fn version_update() -> Vec<u8> {
+    ServerPacket::VersionUpdate.encode()
+}
+
+/// Writes the packet making the client flash its window.
+pub fn get_attention() -> Vec<u8> {
+    ServerPacket::GetAttention.encode()
+}
+
+/// Writes the image shown on the main menu, opening `click_url` on click.
+pub fn main_menu_icon(image_url: &str, click_url: &str) -> Vec<u8> {
+    ServerPacket::MainMenuIcon(format!("{}|{}", image_url, click_url)).encode()
+}
+
+/// Writes the packet telling the client to reconnect after `time` ms.
+pub fn switch_server(time: &i32) -> Vec<u8> {
+    ServerPacket::SwitchServer(*time).encode()
+}
+
+/// Writes the packet moving a tournament client to another server.
+pub fn switch_tournament_server(address: &str) -> Vec<u8> {
+    ServerPacket::SwitchTournamentServer(address.to_string()).encode()
+}
+
+/// Writes the presence of a player (their name, location and rank).
+pub fn user_presence(p: &Player) -> Vec<u8> {
+    ServerPacket::UserPresence(UserPresence {
+        user_id: p.id,
+        name: p.name.clone(),
+        utc_offset: (p.utc_offset as i16 + 24) as u8,
+        country: p.location.country,
+        // Ripple gives supporter to everyone, as does pep.py.
+        bancho_priv: p.privileges.as_bancho_priv(true) | ((p.mode as u8) << 5),
+        longitude: p.location.location.1,
+        latitude: p.location.location.0,
+        rank: p.stats.rank,
+    }).encode()
+}
+
+/// Writes the stats and current action of a player.
+pub fn user_stats(p: &Player) -> Vec<u8> {
+    ServerPacket::UserStats(UserStats {
+        user_id: p.id,
+        action: p.action.id,
+        text: p.action.text.clone(),
+        map_md5: p.action.bmap_md5.clone(),
+        mods: p.action.mods,
+        mode: p.mode as u8,
+        map_id: p.action.bmap_id,
+        ranked_score: p.stats.ranked_score,
+        // Ripple stores accuracy as a percentage, the client expects a fraction.
+        accuracy: p.stats.accuracy / 100.0,
+        playcount: p.stats.playcount,
+        total_score: p.stats.total_score,
+        rank: p.stats.rank,
+        // The client would overflow pp above i16::MAX.
+        pp: p.stats.pp.min(i16::MAX as i32) as i16,
+    }).encode()
+}
+
+/// Writes the presence of a player, for the client to request their
+/// details if needed.
+pub fn user_presence_single(user_id: &i32) -> Vec<u8> {
+    ServerPacket::UserPresenceSingle(*user_id).encode()
+}
+
+/// Writes the ids of a list of online players, for the client to request
+/// their details if needed.
+pub fn user_presence_bundle(user_ids: &[i32]) -> Vec<u8> {
+    ServerPacket::UserPresenceBundle(user_ids.to_vec()).encode()
+}
+
+/// Writes the packet telling clients a player has logged out.
+pub fn user_logout(user_id: &i32) -> Vec<u8> {
+    ServerPacket::UserLogout(UserLogout { user_id: *user_id, reason: 0 }).encode()
+}
+
+/// Writes a chat message.
+pub fn send_message(sender: &str, content: &str, target: &str, sender_id: &i32) -> Vec<u8> {
+    ServerPacket::SendMessage(Message {
+        sender: sender.to_string(),
+        content: content.to_string(),
+        target: target.to_string(),
+        sender_id: *sender_id,
+    }).encode()
+}
+
+/// Writes the packet telling the client how long they are silenced for,
+/// in seconds.
+pub fn silence_end(seconds: &i32) -> Vec<u8> {
+    ServerPacket::SilenceEnd(*seconds).encode()
+}
+
+/// Writes the packet telling clients a player has been silenced, clearing
+/// their messages.
+pub fn user_silenced(user_id: &i32) -> Vec<u8> {
+    ServerPacket::UserSilenced(*user_id).encode()
+}
+
+/// Writes the packet telling the sender of a DM that the target only accepts
+/// DMs from friends.
+pub fn user_dm_blocked(target: &str) -> Vec<u8> {
+    ServerPacket::UserDmBlocked(Message {
+        sender: String::new(),
+        content: String::new(),
+        target: target.to_string(),
+        sender_id: 0,
+    }).encode()
+}
+
+/// Writes the packet telling the sender of a DM that the target is silenced.
+pub fn target_is_silenced(target: &str) -> Vec<u8> {
+    ServerPacket::TargetIsSilenced(Message {
+        sender: String::new(),
+        content: String::new(),
+        target: target.to_string(),
+        sender_id: 0,
+    }).encode()
+}
+
+// Channel packets.
+
+/// Writes the packet confirming that the player joined a channel.
+pub fn channel_join_success(name: &str) -> Vec<u8> {
+    ServerPacket::ChannelJoinSuccess(name.to_string()).encode()
+}
+
+/// Writes a channel listing, shown in the channel list.
+pub fn channel_info(name: &str, topic: &str, player_count: &i16) -> Vec<u8> {
+    ServerPacket::ChannelInfo(ChannelInfo {
+        name: name.to_string(),
+        topic: topic.to_string(),
+        player_count: *player_count,
+    }).encode()
+}
+
+/// Writes a channel listing, making the client join it straight away.
+pub fn channel_auto_join(name: &str, topic: &str, player_count: &i16) -> Vec<u8> {
+    ServerPacket::ChannelAutoJoin(ChannelInfo {
+        name: name.to_string(),
+        topic: topic.to_string(),
+        player_count: *player_count,
+    }).encode()
+}
+
+/// Writes the packet removing the player from a channel.
+pub fn channel_kick(name: &str) -> Vec<u8> {
+    ServerPacket::ChannelKick(name.to_string()).encode()
+}
+
+/// Writes the info of the beatmaps the client asked for.
+pub fn beatmap_info_reply(maps: &[BeatmapInfo]) -> Vec<u8> {
+    ServerPacket::BeatmapInfoReply(BeatmapInfoReply { maps: maps.to_vec() }).encode()
+}
+
+// Spectator packets.
+
+/// Writes the packet telling a host that a player started spectating them.
+pub fn spectator_joined(user_id: &i32) -> Vec<u8> {
+    ServerPacket::SpectatorJoined(*user_id).encode()
+}
+
+/// Writes the packet telling a host that a player stopped spectating them.
+pub fn spectator_left(user_id: &i32) -> Vec<u8> {
+    ServerPacket::SpectatorLeft(*user_id).encode()
+}
+
+/// Writes the packet telling a spectator another player joined the spectators.
+pub fn fellow_spectator_joined(user_id: &i32) -> Vec<u8> {
+    ServerPacket::FellowSpectatorJoined(*user_id).encode()
+}
+
+/// Writes the packet telling a spectator another player left the spectators.
+pub fn fellow_spectator_left(user_id: &i32) -> Vec<u8> {
+    ServerPacket::FellowSpectatorLeft(*user_id).encode()
+}
+
+/// Writes a bundle of spectator frames, as sent by the host.
+pub fn spectate_frames(frames: &[u8]) -> Vec<u8> {
+    ServerPacket::SpectateFrames(Raw(frames.to_vec())).encode()
+}
+
+/// Writes the packet telling that a spectator does not have the map.
+pub fn cant_spectate(user_id: &i32) -> Vec<u8> {
+    ServerPacket::SpectatorCantSpectate(*user_id).encode()
+}
+
+// Multiplayer packets.
+// SRV_ALL_PLAYERS_LOADED and SRV_TOGGLE_BLOCK_NON_FRIEND_DMS are not used by
+// current clients, so have no builders.
+
+/// Builds the full state of a match. The password is only revealed to the
+/// players in the match.
+fn match_data(m: &Match, send_password: bool) -> MatchData {
+    let mut data = MatchData {
+        id: m.id as i16,
+        in_progress: m.in_progress,
+        match_type: 0, // Unused.
+        mods: m.mods,
+        name: m.name.clone(),
+        password: m.password.clone(),
+        map_name: m.map_name.clone(),
+        map_id: m.map_id,
+        map_md5: m.map_md5.clone(),
+        slot_statuses: [0; MAX_SLOTS],
+        slot_teams: [0; MAX_SLOTS],
+        slot_players: [None; MAX_SLOTS],
+        host: m.host,
+        mode: m.mode,
+        win_condition: m.win_condition,
+        team_type: m.team_type,
+        freemods: m.freemods,
+        slot_mods: [0; MAX_SLOTS],
+        seed: m.seed,
+    };
+
+    if !send_password && !m.password.is_empty() {
+        // The client only needs to know there is a password.
+        data.password = " ".to_string();
+    }
+
+    for (i, slot) in m.slots.iter().enumerate() {
+        data.slot_statuses[i] = slot.status;
+        data.slot_teams[i] = slot.team;
+        data.slot_players[i] = slot.player;
+        data.slot_mods[i] = slot.mods;
+    }
+    data
+}
+
+/// Writes a newly created match, for players in the lobby.
+pub fn new_match(m: &Match) -> Vec<u8> {
+    ServerPacket::NewMatch(match_data(m, false)).encode()
+}
+
+/// Writes an update of the state of a match.
+pub fn update_match(m: &Match, send_password: bool) -> Vec<u8> {
+    ServerPacket::UpdateMatch(match_data(m, send_password)).encode()
+}
+
+/// Writes the packet removing a match from the lobby.
+pub fn dispose_match(match_id: &i32) -> Vec<u8> {
+    ServerPacket::DisposeMatch(*match_id).encode()
+}
+
+/// Writes the packet confirming that a player joined a match.
+pub fn match_join_success(m: &Match) -> Vec<u8> {
+    ServerPacket::MatchJoinSuccess(match_data(m, true)).encode()
+}
+
+/// Writes the packet telling a player they could not join a match.
+pub fn match_join_fail() -> Vec<u8> {
+    ServerPacket::MatchJoinFail.encode()
+}
+
+/// Writes the packet starting a match.
+pub fn match_start(m: &Match) -> Vec<u8> {
+    ServerPacket::MatchStart(match_data(m, true)).encode()
+}
+
+/// Writes a player's score frame, as relayed to the match.
+pub fn match_score_update(frame: &[u8]) -> Vec<u8> {
+    ServerPacket::MatchScoreUpdate(Raw(frame.to_vec())).encode()
+}
+
+/// Writes the packet making the recipient the host of their match.
+pub fn match_transfer_host() -> Vec<u8> {
+    ServerPacket::MatchTransferHost.encode()
+}
+
+/// Writes the packet telling all players in a match have loaded the map.
+pub fn match_all_players_loaded() -> Vec<u8> {
+    ServerPacket::MatchAllPlayersLoaded.encode()
+}
+
+/// Writes the packet telling that the player in a slot has failed.
+pub fn match_player_failed(slot_id: &i32) -> Vec<u8> {
+    ServerPacket::MatchPlayerFailed(*slot_id).encode()
+}
+
+/// Writes the packet aborting a match in progress.
+pub fn match_abort() -> Vec<u8> {
+    ServerPacket::MatchAbort.encode()
+}
+
+/// Writes the packet telling that a match has finished.
+pub fn match_complete() -> Vec<u8> {
+    ServerPacket::MatchComplete.encode()
+}
+
+/// Writes the packet telling that the player in a slot wants to skip.
+pub fn match_player_skipped(slot_id: &i32) -> Vec<u8> {
+    ServerPacket::MatchPlayerSkipped(*slot_id).encode()
+}
+
+/// Writes the packet skipping the intro of the map for the whole match.
+pub fn match_skip() -> Vec<u8> {
+    ServerPacket::MatchSkip.encode()
+}
+
+/// Writes the new password of a match.
+pub fn match_change_password(password: &str) -> Vec<u8> {
+    ServerPacket::MatchChangePassword(password.to_string()).encode()
+}
+
+/// Writes an invite to a match, shown as a message from the inviter.
+pub fn match_invite(sender: &str, content: &str, target: &str, sender_id: &i32) -> Vec<u8> {
+    ServerPacket::MatchInvite(Message {
+        sender: sender.to_string(),
+        content: content.to_string(),
+        target: target.to_string(),
+        sender_id: *sender_id,
+    }).encode()
+}
+
+#[cfg(test)]
+mod tests {
+    //! The expected bytes are laid out by hand from the protocol, field by
+    //! field, rather than taken from a client capture.
+    use super::*;
+    use crate::objects::player::{self, Action, Stats};
+    use crate::objects::multiplayer::MatchSettings;
+    use crate::consts::privileges::Privileges;
+
+    /// A player with something set in every field the packets carry.
+    fn test_player() -> Player {
+        let mut p = player::test_player(3, "bob", "");
+        p.location.country = 1;
+        p.location.location = (1.0, -1.0);
+        p.privileges = Privileges::from_bitwise(3);
+        p.action = Action {
+            id: 2,
+            text: "x".to_string(),
+            bmap_md5: String::new(),
+            bmap_id: 7,
+            mods: 64,
+        };
+        p.stats = Stats {
+            ranked_score: 100,
+            total_score: 200,
+            accuracy: 50.0,
+            playcount: 4,
+            rank: 10,
+            pp: 40000,
+        };
+        p.utc_offset = 1;
+        p
+    }
+
+    fn test_match() -> Match {
+        let mut m = Match::new(1, MatchSettings {
+            name: "m".to_string(),
+            password: "pw".to_string(),
+            mods: 0,
+            map_name: String::new(),
+            map_id: 0,
+            map_md5: String::new(),
+            host: 3,
+            mode: 0,
+            win_condition: 0,
+            team_type: 0,
+            freemods: false,
+            seed: 0,
+        });
+        m.place_player(0, 3);
+        m
+    }
+
+    /// The body of `test_match`, starting right after the password.
+    fn test_match_tail() -> Vec<u8> {
+        [
+            &[0][..],          // Map name.
+            &[0, 0, 0, 0],     // Map id.
+            &[0],              // Map md5.
+            &[4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // Slot statuses.
+            &[0; 16],          // Slot teams.
+            &[3, 0, 0, 0],     // Player in slot 0.
+            &[3, 0, 0, 0],     // Host.
+            &[0, 0, 0, 0],     // Mode, win condition, team type, freemods.
+            &[0, 0, 0, 0],     // Seed.
+        ].concat()
+    }
+
+    #[test]
+    fn empty_packets() {
+        assert_eq!(ping(), [8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(channel_info_end(), [89, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(version_update(), [19, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(version_update_forced(), [102, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(account_restricted(), [104, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(get_attention(), [23, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_join_fail(), [37, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_transfer_host(), [50, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_all_players_loaded(), [53, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_complete(), [58, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_skip(), [61, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(match_abort(), [106, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn int_packets() {
+        assert_eq!(login_reply(&1000), [5, 0, 0, 4, 0, 0, 0, 0xe8, 0x03, 0, 0]);
+        assert_eq!(login_reply(&-1), [5, 0, 0, 4, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(server_restart(&5000), [86, 0, 0, 4, 0, 0, 0, 0x88, 0x13, 0, 0]);
+        assert_eq!(protocol_version(&19), [75, 0, 0, 4, 0, 0, 0, 19, 0, 0, 0]);
+        assert_eq!(bancho_privileges(&5), [71, 0, 0, 4, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(switch_server(&0), [103, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(silence_end(&60), [92, 0, 0, 4, 0, 0, 0, 60, 0, 0, 0]);
+        assert_eq!(user_silenced(&3), [94, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(user_presence_single(&3), [95, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(spectator_joined(&3), [13, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(spectator_left(&3), [14, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(fellow_spectator_joined(&3), [42, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(fellow_spectator_left(&3), [43, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(cant_spectate(&3), [22, 0, 0, 4, 0, 0, 0, 3, 0, 0, 0]);
+        assert_eq!(dispose_match(&1), [28, 0, 0, 4, 0, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(match_player_failed(&2), [57, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(match_player_skipped(&2), [81, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn list_packets() {
+        assert_eq!(friends_list(&[1, 2]), [
+            72, 0, 0, 10, 0, 0, 0,
+            2, 0, 1, 0, 0, 0, 2, 0, 0, 0,
+        ]);
+        assert_eq!(friends_list(&[]), [72, 0, 0, 2, 0, 0, 0, 0, 0]);
+        assert_eq!(user_presence_bundle(&[3, 4]), [
+            96, 0, 0, 10, 0, 0, 0,
+            2, 0, 3, 0, 0, 0, 4, 0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn string_packets() {
+        assert_eq!(notification("hello"), [
+            24, 0, 0, 7, 0, 0, 0,
+            0x0b, 5, b'h', b'e', b'l', b'l', b'o',
+        ]);
+        assert_eq!(notification(""), [24, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(main_menu_icon("i.png", "a.b"), [
+            76, 0, 0, 11, 0, 0, 0,
+            0x0b, 9, b'i', b'.', b'p', b'n', b'g', b'|', b'a', b'.', b'b',
+        ]);
+        assert_eq!(switch_tournament_server("1.2.3.4"), [
+            107, 0, 0, 9, 0, 0, 0,
+            0x0b, 7, b'1', b'.', b'2', b'.', b'3', b'.', b'4',
+        ]);
+        assert_eq!(match_change_password("pw"), [91, 0, 0, 4, 0, 0, 0, 0x0b, 2, b'p', b'w']);
+    }
+
+    #[test]
+    fn message_packets() {
+        assert_eq!(send_message("a", "hi", "#osu", &3), [
+            7, 0, 0, 17, 0, 0, 0,
+            0x0b, 1, b'a',                      // Sender.
+            0x0b, 2, b'h', b'i',                // Content.
+            0x0b, 4, b'#', b'o', b's', b'u',    // Target.
+            3, 0, 0, 0,                         // Sender id.
+        ]);
+        assert_eq!(match_invite("a", "hi", "b", &3), [
+            88, 0, 0, 14, 0, 0, 0,
+            0x0b, 1, b'a',
+            0x0b, 2, b'h', b'i',
+            0x0b, 1, b'b',
+            3, 0, 0, 0,
+        ]);
+        assert_eq!(user_dm_blocked("bob"), [
+            100, 0, 0, 11, 0, 0, 0,
+            0, 0, 0x0b, 3, b'b', b'o', b'b', 0, 0, 0, 0,
+        ]);
+        assert_eq!(target_is_silenced("bob"), [
+            101, 0, 0, 11, 0, 0, 0,
+            0, 0, 0x0b, 3, b'b', b'o', b'b', 0, 0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn channel_packets() {
+        let body = [
+            0x0b, 4, b'#', b'o', b's', b'u',
+            0x0b, 4, b'M', b'a', b'i', b'n',
+            5, 0,
+        ];
+        assert_eq!(channel_info("#osu", "Main", &5), [&[65, 0, 0, 14, 0, 0, 0][..], &body].concat());
+        assert_eq!(channel_auto_join("#osu", "Main", &5), [&[67, 0, 0, 14, 0, 0, 0][..], &body].concat());
+        assert_eq!(channel_join_success("#osu"), [
+            64, 0, 0, 6, 0, 0, 0,
+            0x0b, 4, b'#', b'o', b's', b'u',
+        ]);
+        assert_eq!(channel_kick("#osu"), [
+            66, 0, 0, 6, 0, 0, 0,
+            0x0b, 4, b'#', b'o', b's', b'u',
+        ]);
+    }
+
+    #[test]
+    fn user_packets() {
+        let p = test_player();
+        assert_eq!(user_presence(&p), [
+            83, 0, 0, 24, 0, 0, 0,
+            3, 0, 0, 0,                 // User id.
+            0x0b, 3, b'b', b'o', b'b',  // Name.
+            25,                         // UTC offset.
+            1,                          // Country.
+            5,                          // Player | Supporter, osu!standard.
+            0, 0, 0x80, 0xbf,           // Longitude.
+            0, 0, 0x80, 0x3f,           // Latitude.
+            10, 0, 0, 0,                // Rank.
+        ]);
+        assert_eq!(user_stats(&p), [
+            11, 0, 0, 48, 0, 0, 0,
+            3, 0, 0, 0,                 // User id.
+            2,                          // Action.
+            0x0b, 1, b'x',              // Action text.
+            0,                          // Map md5.
+            64, 0, 0, 0,                // Mods.
+            0,                          // Mode.
+            7, 0, 0, 0,                 // Map id.
+            100, 0, 0, 0, 0, 0, 0, 0,   // Ranked score.
+            0, 0, 0, 0x3f,              // Accuracy (0.5).
+            4, 0, 0, 0,                 // Playcount.
+            200, 0, 0, 0, 0, 0, 0, 0,   // Total score.
+            10, 0, 0, 0,                // Rank.
+            0xff, 0x7f,                 // pp, capped to i16::MAX.
+        ]);
+        assert_eq!(user_logout(&3), [12, 0, 0, 5, 0, 0, 0, 3, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn frame_packets() {
+        assert_eq!(spectate_frames(&[1, 2, 3]), [15, 0, 0, 3, 0, 0, 0, 1, 2, 3]);
+        assert_eq!(match_score_update(&[1, 2, 3]), [48, 0, 0, 3, 0, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn match_packets() {
+        let m = test_match();
+        let head = [
+            1, 0,               // Match id.
+            0,                  // In progress.
+            0,                  // Match type.
+            0, 0, 0, 0,         // Mods.
+            0x0b, 1, b'm',      // Name.
+        ];
+
+        // The lobby only learns that there is a password.
+        let hidden = [&head[..], &[0x0b, 1, b' '], &test_match_tail()].concat();
+        assert_eq!(new_match(&m), [&[27, 0, 0, 68, 0, 0, 0][..], &hidden].concat());
+        assert_eq!(update_match(&m, false), [&[26, 0, 0, 68, 0, 0, 0][..], &hidden].concat());
+
+        let shown = [&head[..], &[0x0b, 2, b'p', b'w'], &test_match_tail()].concat();
+        assert_eq!(update_match(&m, true), [&[26, 0, 0, 69, 0, 0, 0][..], &shown].concat());
+        assert_eq!(match_join_success(&m), [&[36, 0, 0, 69, 0, 0, 0][..], &shown].concat());
+        assert_eq!(match_start(&m), [&[46, 0, 0, 69, 0, 0, 0][..], &shown].concat());
+    }
+
+    #[test]
+    fn beatmap_info_packets() {
+        let map = BeatmapInfo {
+            index: 0,
+            map_id: 1,
+            set_id: 2,
+            thread_id: 0,
+            status: 2,
+            osu_grade: 0,
+            catch_grade: 9,
+            taiko_grade: 9,
+            mania_grade: 9,
+            map_md5: "abc".to_string(),
+        };
+        assert_eq!(beatmap_info_reply(&[map]), [
+            69, 0, 0, 28, 0, 0, 0,
+            1, 0, 0, 0,                 // Map count.
+            0, 0,                       // Index.
+            1, 0, 0, 0,                 // Map id.
+            2, 0, 0, 0,                 // Set id.
+            0, 0, 0, 0,                 // Thread id.
+            2,                          // Status.
+            0, 9, 9, 9,                 // Grades.
+            0x0b, 3, b'a', b'b', b'c',  // Map md5.
+        ]);
+    }
 }
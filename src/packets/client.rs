@@ -0,0 +1,109 @@
+// Packets sent by the osu! client.
+use crate::packets::packet::{osu_packet, packet_enum, OsuEncode, Raw, MatchData};
+use crate::packets::rw::{Writer, Reader, ReadError, Message};
+
+packet_enum! {
+    /// # Client Packet
+    /// A packet sent by the client, alongside its decoded payload. Ids not
+    /// listed here (such as the Aeris group packets) fail to decode with
+    /// `ReadError::UnknownPacket`.
+    pub enum ClientPacket {
+        OSU_CHANGE_ACTION => ChangeAction(ChangeAction),
+        OSU_SEND_PUBLIC_MESSAGE => SendPublicMessage(Message),
+        OSU_LOGOUT => Logout,
+        OSU_REQUEST_STATUS_UPDATE => RequestStatusUpdate,
+        OSU_PING => Ping,
+        OSU_START_SPECTATING => StartSpectating(i32),
+        OSU_STOP_SPECTATING => StopSpectating,
+        OSU_SPECTATE_FRAMES => SpectateFrames(Raw),
+        OSU_ERROR_REPORT => ErrorReport(Raw),
+        OSU_CANT_SPECTATE => CantSpectate,
+        OSU_SEND_PRIVATE_MESSAGE => SendPrivateMessage(Message),
+        OSU_PART_LOBBY => PartLobby,
+        OSU_JOIN_LOBBY => JoinLobby,
+        OSU_CREATE_MATCH => CreateMatch(MatchData),
+        OSU_JOIN_MATCH => JoinMatch(JoinMatch),
+        OSU_PART_MATCH => PartMatch,
+        OSU_MATCH_CHANGE_SLOT => MatchChangeSlot(i32),
+        OSU_MATCH_READY => MatchReady,
+        OSU_MATCH_LOCK => MatchLock(i32),
+        OSU_MATCH_CHANGE_SETTINGS => MatchChangeSettings(MatchData),
+        OSU_MATCH_START => MatchStart,
+        OSU_MATCH_SCORE_UPDATE => MatchScoreUpdate(Raw),
+        OSU_MATCH_COMPLETE => MatchComplete,
+        OSU_MATCH_CHANGE_MODS => MatchChangeMods(u32),
+        OSU_MATCH_LOAD_COMPLETE => MatchLoadComplete,
+        OSU_MATCH_NO_BEATMAP => MatchNoBeatmap,
+        OSU_MATCH_UNREADY => MatchUnready,
+        OSU_MATCH_FAILED => MatchFailed,
+        OSU_MATCH_HAS_BEATMAP => MatchHasBeatmap,
+        OSU_MATCH_SKIP_REQUEST => MatchSkipRequest,
+        OSU_CHANNEL_JOIN => ChannelJoin(String),
+        OSU_BEATMAP_INFO_REQUEST => BeatmapInfoRequest(BeatmapInfoRequest),
+        OSU_MATCH_TRANSFER_HOST => MatchTransferHost(i32),
+        OSU_FRIEND_ADD => FriendAdd(i32),
+        OSU_FRIEND_REMOVE => FriendRemove(i32),
+        OSU_MATCH_CHANGE_TEAM => MatchChangeTeam,
+        OSU_CHANNEL_PART => ChannelPart(String),
+        OSU_RECEIVE_UPDATES => ReceiveUpdates(i32),
+        OSU_SET_AWAY_MESSAGE => SetAwayMessage(Message),
+        OSU_USER_STATS_REQUEST => UserStatsRequest(Vec<i32>),
+        OSU_MATCH_INVITE => MatchInvite(i32),
+        OSU_MATCH_CHANGE_PASSWORD => MatchChangePassword(MatchData),
+        OSU_TOURNAMENT_MATCH_INFO_REQUEST => TournamentMatchInfoRequest(i32),
+        OSU_USER_PRESENCE_REQUEST => UserPresenceRequest(Vec<i32>),
+        OSU_USER_PRESENCE_REQUEST_ALL => UserPresenceRequestAll(i32),
+        OSU_TOGGLE_BLOCK_NON_FRIEND_DMS => ToggleBlockNonFriendDms(i32),
+        OSU_TOURNAMENT_JOIN_MATCH_CHANNEL => TournamentJoinMatchChannel(i32),
+        OSU_TOURNAMENT_LEAVE_MATCH_CHANNEL => TournamentLeaveMatchChannel(i32),
+    }
+}
+
+osu_packet! {
+    /// The player's new in-game action (eg playing a map).
+    pub struct ChangeAction {
+        pub action: u8,
+        pub text: String,
+        pub map_md5: String,
+        pub mods: u32,
+        pub mode: u8,
+        pub map_id: i32,
+    }
+}
+
+osu_packet! {
+    /// A request to join a match.
+    pub struct JoinMatch {
+        pub match_id: i32,
+        pub password: String,
+    }
+}
+
+/// A request for the info of a set of beatmaps, by filename and by id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatmapInfoRequest {
+    pub filenames: Vec<String>,
+    pub ids: Vec<i32>,
+}
+
+// Unlike other lists, both of these are prefixed by an i32 length.
+impl OsuEncode for BeatmapInfoRequest {
+    fn encode(&self, w: &mut Writer) {
+        w.write_int(&(self.filenames.len() as i32));
+        for filename in self.filenames.iter() { w.write_string(filename); }
+        w.write_int(&(self.ids.len() as i32));
+        for id in self.ids.iter() { w.write_int(id); }
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        // Not preallocating, as the lengths come straight from the client.
+        let filenames = (0..r.read_int::<i32>()?)
+            .map(|_| r.read_string())
+            .collect::<Result<_, _>>()?;
+        let ids = (0..r.read_int::<i32>()?)
+            .map(|_| r.read_int())
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { filenames, ids })
+    }
+}
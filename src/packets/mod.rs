@@ -1,3 +1,6 @@
 pub mod rw;
+pub mod packet;
+pub mod client;
+pub mod server;
 pub mod router;
 pub mod builders;
@@ -0,0 +1,448 @@
+// Typed packet structures, encoded on top of the packet reader and writer.
+use crate::packets::rw::{Writer, Reader, ReadError, PacketVector};
+use crate::objects::multiplayer::{MAX_SLOTS, SLOT_HAS_PLAYER};
+
+/// # Osu Encode
+/// A value that can be written to, and read back from, a packet body.
+pub trait OsuEncode: Sized {
+    fn encode(&self, w: &mut Writer);
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError>;
+}
+
+/// # Osu Packet
+/// A structure making up the whole body of a packet, sent under `ID`.
+pub trait OsuPacket: OsuEncode {
+    const ID: u16;
+
+    /// Writes the full packet, headers included.
+    fn write(&self) -> Vec<u8> {
+        let mut w = Writer::new(Self::ID);
+        self.encode(&mut w);
+        w.build()
+    }
+
+    /// Reads the packet from its body (excluding the headers).
+    fn read(body: &[u8]) -> Result<Self, ReadError> {
+        Self::decode(&mut Reader::new(body))
+    }
+}
+
+impl<T: PacketVector> OsuEncode for T {
+    #[inline(always)]
+    fn encode(&self, w: &mut Writer) {
+        w.write_int(self);
+    }
+
+    #[inline(always)]
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        r.read_int()
+    }
+}
+
+// osu! sends bools as a single byte.
+impl OsuEncode for bool {
+    fn encode(&self, w: &mut Writer) {
+        w.write_int(&(*self as u8));
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        Ok(r.read_int::<u8>()? != 0)
+    }
+}
+
+impl OsuEncode for String {
+    fn encode(&self, w: &mut Writer) {
+        w.write_string(self);
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        r.read_string()
+    }
+}
+
+impl OsuEncode for Vec<i32> {
+    fn encode(&self, w: &mut Writer) {
+        w.write_i32_list(self);
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        r.read_i32_l()
+    }
+}
+
+/// # Osu Packet Macro
+/// Declares a struct alongside its `OsuEncode` implementation, which writes
+/// and reads the fields in the order they are declared. Fields may be of any
+/// `OsuEncode` type, including other structs declared with this macro.
+///
+/// Adding `#[id = ...]` after the doc comment also implements `OsuPacket`,
+/// making the struct a packet of its own.
+///
+/// ```ignore
+/// osu_packet! {
+///     /// Tells the client its user id (or why the login failed).
+///     #[id = packet_ids::SRV_USER_ID]
+///     pub struct LoginReply {
+///         pub user_id: i32,
+///     }
+/// }
+/// ```
+macro_rules! osu_packet {
+    (
+        $(#[doc = $doc:expr])*
+        #[id = $id:expr]
+        pub struct $name:ident {
+            $( $(#[doc = $f_doc:expr])* pub $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $crate::packets::packet::osu_packet! {
+            $(#[doc = $doc])*
+            pub struct $name {
+                $( $(#[doc = $f_doc])* pub $field: $ty ),*
+            }
+        }
+
+        impl $crate::packets::packet::OsuPacket for $name {
+            const ID: u16 = $id;
+        }
+    };
+    (
+        $(#[doc = $doc:expr])*
+        pub struct $name:ident {
+            $( $(#[doc = $f_doc:expr])* pub $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $(#[doc = $doc])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name {
+            $( $(#[doc = $f_doc])* pub $field: $ty ),*
+        }
+
+        impl $crate::packets::packet::OsuEncode for $name {
+            #[allow(unused_variables)]
+            fn encode(&self, w: &mut $crate::packets::rw::Writer) {
+                $( $crate::packets::packet::OsuEncode::encode(&self.$field, w); )*
+            }
+
+            #[allow(unused_variables)]
+            fn decode(
+                r: &mut $crate::packets::rw::Reader<'_>,
+            ) -> Result<Self, $crate::packets::rw::ReadError> {
+                // Struct fields are evaluated in the order they are written.
+                Ok(Self {
+                    $( $field: $crate::packets::packet::OsuEncode::decode(r)?, )*
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use osu_packet;
+
+/// # Packet Enum Macro
+/// Declares an enum with one variant per packet id, each optionally carrying
+/// an `OsuEncode` payload, alongside `id`, `decode` and `encode` functions.
+/// Ids are the names of the constants in `consts::packet_ids`.
+///
+/// ```ignore
+/// packet_enum! {
+///     pub enum ClientPacket {
+///         OSU_PING => Ping,
+///         OSU_FRIEND_ADD => FriendAdd(i32),
+///     }
+/// }
+/// ```
+macro_rules! packet_enum {
+    (
+        $(#[doc = $doc:expr])*
+        pub enum $name:ident {
+            $( $id:ident => $variant:ident $( ($ty:ty) )? ),* $(,)?
+        }
+    ) => {
+        $(#[doc = $doc])*
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum $name {
+            $( $variant $( ($ty) )? ),*
+        }
+
+        impl $name {
+            /// Returns the id the packet is sent under.
+            pub fn id(&self) -> u16 {
+                match self {
+                    $( $crate::packets::packet::packet_enum!(@pat $variant $(, $ty)?)
+                        => $crate::consts::packet_ids::$id, )*
+                }
+            }
+
+            /// Decodes the body of the packet with the given id.
+            pub fn decode(id: u16, body: &[u8]) -> Result<Self, $crate::packets::rw::ReadError> {
+                #[allow(unused_variables, unused_mut)]
+                let mut r = $crate::packets::rw::Reader::new(body);
+                Ok(match id {
+                    $( $crate::consts::packet_ids::$id => Self::$variant $(
+                        (<$ty as $crate::packets::packet::OsuEncode>::decode(&mut r)?)
+                    )?, )*
+                    _ => return Err($crate::packets::rw::ReadError::UnknownPacket(id)),
+                })
+            }
+
+            /// Encodes the packet, headers included.
+            pub fn encode(&self) -> Vec<u8> {
+                #[allow(unused_mut)]
+                let mut w = $crate::packets::rw::Writer::new(self.id());
+                match self {
+                    $( $crate::packets::packet::packet_enum!(@bind payload, $variant $(, $ty)?)
+                        => $crate::packets::packet::packet_enum!(@encode w, payload $(, $ty)?), )*
+                }
+                w.build()
+            }
+        }
+    };
+
+    // Helpers dealing with variants with and without a payload.
+    (@pat $variant:ident, $ty:ty) => { Self::$variant(_) };
+    (@pat $variant:ident) => { Self::$variant };
+    (@bind $v:ident, $variant:ident, $ty:ty) => { Self::$variant($v) };
+    (@bind $v:ident, $variant:ident) => { Self::$variant };
+    (@encode $w:ident, $v:ident, $ty:ty) => {
+        $crate::packets::packet::OsuEncode::encode($v, &mut $w)
+    };
+    (@encode $w:ident, $v:ident) => { () };
+}
+
+pub(crate) use packet_enum;
+
+// Payloads shared by several packets.
+
+/// # Raw
+/// The rest of a packet body, kept as is. Used for data the server only
+/// relays, such as replay and score frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raw(pub Vec<u8>);
+
+impl OsuEncode for Raw {
+    fn encode(&self, w: &mut Writer) {
+        w.write_bytes(&self.0);
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        Ok(Self(r.read_to_end().to_vec()))
+    }
+}
+
+/// # Match Data
+/// The full state of a multiplayer match, as sent both by the client (when
+/// creating or editing a match) and by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchData {
+    pub id: i16,
+    pub in_progress: bool,
+    pub match_type: u8,
+    pub mods: u32,
+    pub name: String,
+    pub password: String,
+    pub map_name: String,
+    pub map_id: i32,
+    pub map_md5: String,
+    pub slot_statuses: [u8; MAX_SLOTS],
+    pub slot_teams: [u8; MAX_SLOTS],
+    /// Only sent for slots whose status says they hold a player.
+    pub slot_players: [Option<i32>; MAX_SLOTS],
+    pub host: i32,
+    pub mode: u8,
+    pub win_condition: u8,
+    pub team_type: u8,
+    pub freemods: bool,
+    /// Only sent when freemods is enabled.
+    pub slot_mods: [u32; MAX_SLOTS],
+    pub seed: i32,
+}
+
+impl OsuEncode for MatchData {
+    fn encode(&self, w: &mut Writer) {
+        w.write_int(&self.id);
+        self.in_progress.encode(w);
+        w.write_int(&self.match_type);
+        w.write_int(&self.mods);
+        w.write_string(&self.name);
+        w.write_string(&self.password);
+        w.write_string(&self.map_name);
+        w.write_int(&self.map_id);
+        w.write_string(&self.map_md5);
+
+        for status in self.slot_statuses.iter() { w.write_int(status); }
+        for team in self.slot_teams.iter() { w.write_int(team); }
+        for (status, player) in self.slot_statuses.iter().zip(self.slot_players.iter()) {
+            if status & SLOT_HAS_PLAYER > 0 {
+                w.write_int(&player.unwrap_or(0));
+            }
+        }
+
+        w.write_int(&self.host);
+        w.write_int(&self.mode);
+        w.write_int(&self.win_condition);
+        w.write_int(&self.team_type);
+        self.freemods.encode(w);
+        if self.freemods {
+            for mods in self.slot_mods.iter() { w.write_int(mods); }
+        }
+        w.write_int(&self.seed);
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        let id = r.read_int()?;
+        let in_progress = bool::decode(r)?;
+        let match_type = r.read_int()?;
+        let mods = r.read_int()?;
+        let name = r.read_string()?;
+        let password = r.read_string()?;
+        let map_name = r.read_string()?;
+        let map_id = r.read_int()?;
+        let map_md5 = r.read_string()?;
+
+        let mut slot_statuses = [0; MAX_SLOTS];
+        for status in slot_statuses.iter_mut() { *status = r.read_int()?; }
+        let mut slot_teams = [0; MAX_SLOTS];
+        for team in slot_teams.iter_mut() { *team = r.read_int()?; }
+        let mut slot_players = [None; MAX_SLOTS];
+        for (status, player) in slot_statuses.iter().zip(slot_players.iter_mut()) {
+            if status & SLOT_HAS_PLAYER > 0 {
+                *player = Some(r.read_int()?);
+            }
+        }
+
+        let host = r.read_int()?;
+        let mode = r.read_int()?;
+        let win_condition = r.read_int()?;
+        let team_type = r.read_int()?;
+        let freemods = bool::decode(r)?;
+        let mut slot_mods = [0; MAX_SLOTS];
+        if freemods {
+            for mods in slot_mods.iter_mut() { *mods = r.read_int()?; }
+        }
+        let seed = r.read_int()?;
+
+        Ok(Self {
+            id,
+            in_progress,
+            match_type,
+            mods,
+            name,
+            password,
+            map_name,
+            map_id,
+            map_md5,
+            slot_statuses,
+            slot_teams,
+            slot_players,
+            host,
+            mode,
+            win_condition,
+            team_type,
+            freemods,
+            slot_mods,
+            seed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::{client::ClientPacket, server::ServerPacket, rw::Message};
+    use crate::consts::packet_ids;
+
+    fn test_match_data(freemods: bool) -> MatchData {
+        let mut slot_statuses = [1; MAX_SLOTS];
+        slot_statuses[0] = 4;
+        slot_statuses[3] = 8;
+        slot_statuses[5] = 2;
+        let mut slot_players = [None; MAX_SLOTS];
+        slot_players[0] = Some(3);
+        slot_players[3] = Some(1000);
+
+        MatchData {
+            id: 12,
+            in_progress: true,
+            match_type: 0,
+            mods: 64,
+            name: "test match".to_string(),
+            password: "pw".to_string(),
+            map_name: "Artist - Title [Diff]".to_string(),
+            map_id: 75,
+            map_md5: "a5b99395a42bd55bc5eb1d2411cbdf8b".to_string(),
+            slot_statuses,
+            slot_teams: [0; MAX_SLOTS],
+            slot_players,
+            host: 3,
+            mode: 1,
+            win_condition: 3,
+            team_type: 2,
+            freemods,
+            slot_mods: if freemods { [8; MAX_SLOTS] } else { [0; MAX_SLOTS] },
+            seed: -5,
+        }
+    }
+
+    #[test]
+    fn match_data_round_trips() {
+        for &freemods in [false, true].iter() {
+            let data = test_match_data(freemods);
+            let packet = ServerPacket::UpdateMatch(data.clone()).encode();
+
+            let mut r = Reader::new(&packet);
+            let (id, len) = r.read_headers().unwrap();
+            assert_eq!(id, packet_ids::SRV_UPDATE_MATCH);
+            let body = r.read_bytes(len as usize).unwrap();
+            assert!(r.empty());
+
+            assert_eq!(ServerPacket::decode(id, body).unwrap(), ServerPacket::UpdateMatch(data));
+        }
+    }
+
+    #[test]
+    fn client_packets_round_trip() {
+        let packets = vec![
+            ClientPacket::Ping,
+            ClientPacket::StartSpectating(3),
+            ClientPacket::UserStatsRequest(vec![1, 2, 3]),
+            ClientPacket::SendPublicMessage(Message {
+                sender: String::new(),
+                content: "hello".to_string(),
+                target: "#osu".to_string(),
+                sender_id: 0,
+            }),
+            ClientPacket::CreateMatch(test_match_data(true)),
+            ClientPacket::SpectateFrames(Raw(vec![1, 2, 3, 4])),
+        ];
+
+        // All the packets are sent in a single body, as the client does.
+        let stream: Vec<u8> = packets.iter().flat_map(|p| p.encode()).collect();
+        let mut r = Reader::new(&stream);
+        for packet in packets {
+            let (id, len) = r.read_headers().unwrap();
+            let body = r.read_bytes(len as usize).unwrap();
+            assert_eq!(ClientPacket::decode(id, body).unwrap(), packet);
+        }
+        assert!(r.empty());
+    }
+
+    #[test]
+    fn unknown_packets_fail() {
+        assert!(matches!(
+            ClientPacket::decode(packet_ids::SRV_USER_ID, &[]),
+            Err(ReadError::UnknownPacket(5))
+        ));
+        assert!(matches!(ClientPacket::decode(u16::MAX, &[]), Err(ReadError::UnknownPacket(_))));
+    }
+
+    #[test]
+    fn truncated_packets_fail() {
+        let packet = ClientPacket::CreateMatch(test_match_data(false)).encode();
+        let body = &packet[7..packet.len() - 1];
+        assert!(matches!(
+            ClientPacket::decode(packet_ids::OSU_CREATE_MATCH, body),
+            Err(ReadError::Truncated)
+        ));
+    }
+}
@@ -1,40 +1,65 @@
 use crate::objects::player::{Player, PlayerList};
-use crate::packets::{rw::Reader, builders};
+use crate::objects::multiplayer::MatchList;
+use crate::objects::channel::{Channel, ChannelList};
+use crate::objects::filter::WordFilter;
+use crate::packets::{rw::{Reader, ReadError}, client::ClientPacket, builders};
 use crate::web::server::RequestContext;
 use crate::logger;
-use crate::consts::packet_ids;
-use std::sync::Arc;
-use std::collections::HashMap;
-use tokio::sync::{RwLock, Mutex};
+use crate::config::Config;
+use crate::db::{Database, channels};
+use crate::geoip::GeoIp;
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use std::time::{Duration, Instant};
+use std::path::Path;
+use tokio::sync::RwLock;
+use dashmap::DashMap;
 use ntex::http::Response;
 
 use crate::events::{
+    self,
     login,
-    misc,
+    logout,
 };
 
 /// # Bancho Server
 pub struct BanchoServer {
-    online_players: PlayerList,
+    pub online_players: PlayerList,
+    pub lobby: PlayerList,
+    pub matches: MatchList,
+    pub channels: ChannelList,
+    pub filter: WordFilter,
+    #[allow(dead_code)]
     all_players: PlayerList,
-    uuid_store: Mutex<HashMap<String, i32>>,
+    /// Packets left for kicked players, by token, delivered on their client's
+    /// next request.
+    pub kicked: DashMap<String, (Instant, Vec<u8>)>,
 
-    total_conns: u64,
+    pub config: Config,
+    pub db: Database,
+    pub geoip: Option<GeoIp>,
+
+    total_conns: AtomicU64,
 }
 
 impl BanchoServer {
-    pub fn new() -> Self {
+    pub fn new(config: Config, db: Database, geoip: Option<GeoIp>) -> Self {
         Self {
             online_players: PlayerList::new(),
+            lobby: PlayerList::new(),
+            matches: MatchList::new(),
+            channels: ChannelList::new(),
+            filter: WordFilter::new(),
             all_players: PlayerList::new(),
-            uuid_store: Mutex::new(HashMap::new()),
-            total_conns: 0,
+            kicked: DashMap::new(),
+            config,
+            db,
+            geoip,
+            total_conns: AtomicU64::new(0),
         }
     }
 
-    pub async fn handle_conn(&mut self, req: RequestContext) -> Response {
-        self.total_conns += 1;
-
+    pub async fn handle_conn(&self, req: RequestContext) -> Response {
+        self.total_conns.fetch_add(1, Ordering::Relaxed);
 
         match req.header_value("User-Agent") {
             Some("osu!") => {
@@ -50,21 +75,15 @@ impl BanchoServer {
 
     async fn handle_bancho(&self, mut req: RequestContext) -> Response {
         // If cho-token is not sent, this is a login request.
-        let uuid: String;
-        let packet_resp: Vec<u8>;
-
-        match req.header_value("osu-token") {
+        let (uuid, packet_resp) = match req.header_value("osu-token") {
             // Handle normal packets.
             Some(token) => {
-                uuid = token.to_string();
-                packet_resp = self.handle_packets(req, &uuid).await;
+                let uuid = token.to_string();
+                let packet_resp = self.handle_packets(req, &uuid).await;
+                (uuid, packet_resp)
             },
             // Login request.
-            _ => {
-                let login_resp = login::login_handle(&mut req, &self).await;
-                uuid = login_resp.0;
-                packet_resp = login_resp.1
-            }
+            _ => login::login_handle(&mut req, self).await,
         };
 
         // Build final response
@@ -74,65 +93,173 @@ impl BanchoServer {
     }
 
     // Handles a packet stream from osu.
-    async fn handle_packets(&self, mut req: RequestContext, uuid: &String) -> Vec<u8> {
+    async fn handle_packets(&self, mut req: RequestContext, uuid: &str) -> Vec<u8> {
         // Fetch Player Obj
-        let p: Arc<RwLock<Player>>;
-        match self.player_from_uuid(uuid).await {
-            Some(pl) => {p = pl;}
-            _ => {return builders::server_restart(&0);}
-        }
-        // Create Packet Context
-        let mut ctx = PacketContext {
-            player: p,
-            reader: Reader::new(req.read_body().await),
-            server: &self
+        let p: Arc<RwLock<Player>> = match self.online_players.get_by_token(uuid) {
+            Some(pl) => pl,
+            _ => {
+                // Let a kicked player know why before they reconnect.
+                let mut resp = self.kicked.remove(uuid).map(|(_, (_, packets))| packets).unwrap_or_default();
+                resp.extend(builders::server_restart(&0));
+                return resp;
+            }
         };
+        p.write().await.last_seen = Instant::now();
+        let body = req.read_body().await;
+        let mut reader = Reader::new(&body);
+
+        while !reader.empty() {
+            // A malformed header or length means we lost track of where the
+            // packets are, so the rest of the body is unusable.
+            let packet = reader.read_headers().and_then(|(p_id, p_len)| {
+                Ok((p_id, reader.read_bytes(p_len as usize)?))
+            });
+            let (p_id, body) = match packet {
+                Ok(r) => r,
+                Err(e) => {
+                    logger::warn(format!("Dropping the rest of a malformed packet stream: {}", e));
+                    break;
+                }
+            };
 
-        while !ctx.reader.empty() {
-            let (p_id, p_len) = ctx.reader.read_headers();
-            match p_id {
-                // Handle individual packets.
-                packet_ids::OSU_PING => {misc::handle_ping(&ctx).await}
-                // If we do not have a handler for it, incr buffer.
-                _ => {
+            // Each packet is decoded from just its own body, so a malformed
+            // packet cannot desync the stream.
+            let packet = match ClientPacket::decode(p_id, body) {
+                Ok(packet) => packet,
+                Err(ReadError::UnknownPacket(_)) => {
                     logger::debug(format!("No handler for packet with id {}", p_id));
-                    ctx.reader.incr_buffer(p_len as usize);
+                    continue;
+                },
+                Err(e) => {
+                    logger::warn(format!("Dropping malformed packet with id {}: {}", p_id, e));
+                    continue;
                 }
-            }
+            };
+
+            let mut ctx = PacketContext {
+                player: p.clone(),
+                server: self
+            };
+            events::handle_packet(packet, &mut ctx).await;
         }
 
-        vec![]
+        // Deliver everything queued for the player, including by the packets
+        // just handled.
+        let resp = p.read().await.queue.empty().await;
+        resp
     }
 
-    /// # Player From UUID
-    pub async fn player_from_uuid(&self, uuid: &String) -> Option<Arc<RwLock<Player>>> {
-        if let Some(player_id) = self.uuid_store.lock().await.get(uuid) {
-            self.online_players.get(player_id.clone()).await
-        } else {
-            None
+    /// # Notify Staff
+    /// Sends a notification to all online staff able to manage users.
+    pub async fn notify_staff(&self, msg: &str) {
+        let packet = builders::notification(msg);
+        for player in self.online_players.all() {
+            let p = player.read().await;
+            if p.privileges.manage_users() {
+                p.queue.enqueue(packet.clone()).await;
+            }
         }
     }
+
+    /// # Time Out Idle Players
+    /// Logs out every online player that has not made a request in `timeout`,
+    /// clearing out clients that crashed or lost connection without logging
+    /// out.
+    pub async fn time_out_idle_players(&self, timeout: Duration) {
+        for player in self.online_players.all() {
+            let idle = player.read().await.last_seen.elapsed() >= timeout;
+            if idle {
+                logout::kick(self, &player, "Timed out.").await;
+            }
+        }
+    }
+}
+
+/// How often the online players are checked for idle sessions.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// # Idle Timeout Loop
+/// Periodically times out idle players, as configured by `idle_timeout`. Meant
+/// to be spawned as a background task for the lifetime of the server.
+pub async fn idle_timeout_loop(server: Arc<BanchoServer>) {
+    if server.config.idle_timeout == 0 {
+        return;
+    }
+
+    let timeout = Duration::from_secs(server.config.idle_timeout);
+    let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        server.time_out_idle_players(timeout).await;
+    }
 }
 
 /// A context struct provided to all packet events.
 pub struct PacketContext<'a> {
     pub player: Arc<RwLock<Player>>,
-    pub reader: Reader,
     pub server: &'a BanchoServer,
 }
 
-// This is really weird but i have a headache.
-static mut bancho_server: Option<BanchoServer> = None;
+/// # Create Bancho Server
+/// Connects to the services cu.rs depends on, creating the server state shared
+/// by all of the web server's workers.
+pub async fn create_bancho_server(config: Config) -> Arc<BanchoServer> {
+    let db = match Database::connect(&config).await {
+        Ok(db) => db,
+        Err(e) => {
+            logger::error(format!("Failed to connect to the database: {}", e));
+            panic!("Could not connect to MySQL!");
+        }
+    };
+    logger::info("Connected to the database.");
 
-pub async fn create_bancho_server() {
-    unsafe {
-        bancho_server = Some(BanchoServer::new());
+    let geoip = match GeoIp::open(&config.geoip_db) {
+        Ok(g) => Some(g),
+        Err(e) => {
+            logger::warn(format!("Failed to load the GeoIP database, geolocation is disabled: {}", e));
+            None
+        }
+    };
+
+    let server = BanchoServer::new(config, db, geoip);
+    load_channels(&server).await;
+    load_filter(&server).await;
+
+    Arc::new(server)
+}
+
+/// # Load Filter
+/// Loads the chat word filter rules from the configured file, if present.
+async fn load_filter(srv: &BanchoServer) {
+    if !Path::new(&srv.config.filter_file).exists() {
+        logger::info("No word filter file found, chat will not be filtered.");
+        return;
+    }
+
+    match srv.filter.load(&srv.config.filter_file).await {
+        Ok(count) => logger::info(format!("Loaded {} word filter rules.", count)),
+        Err(e) => logger::error(format!("Failed to load the word filter: {}", e)),
     }
 }
 
-/// A really wacky, hacky solution to get back into the class.
-pub async fn handle_bancho(req: RequestContext) -> Response {
-    unsafe {
-        return bancho_server.as_mut().unwrap().handle_conn(req).await
+/// # Load Channels
+/// Loads the chat channels from the database into the server.
+async fn load_channels(srv: &BanchoServer) {
+    let rows = match channels::fetch_all(&srv.db).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            logger::error(format!("Failed to load the chat channels: {}", e));
+            return;
+        }
     };
+
+    for row in rows {
+        let mut channel = Channel::new(row.name, row.description);
+        channel.public_read = row.public_read != 0;
+        channel.public_write = row.public_write != 0;
+        channel.hidden = row.hidden != 0;
+        channel.auto_join = srv.config.auto_join_channels.contains(&channel.name);
+        srv.channels.add(channel);
+    }
+    logger::info(format!("Loaded {} chat channels.", srv.channels.all().len()));
 }
@@ -1,3 +1,6 @@
+use std::convert::TryInto;
+use crate::packets::packet::{osu_packet, OsuEncode};
+
 // Packet Writer.
 pub struct Writer {
     id: u16,
@@ -35,12 +38,17 @@ impl Writer {
         }
     }
 
+    /// Writes raw bytes to the buffer, as they are.
+    pub fn write_bytes(&mut self, b: &[u8]) {
+        self.buffer.extend_from_slice(b);
+    }
+
     /// Writes an osu style list of integers to the buffer
-    fn write_i32_list(&mut self, l: Vec<i32>) {
+    pub fn write_i32_list(&mut self, l: &[i32]) {
         let l_len = l.len() as u16;
         self.write_int(&l_len);
 
-        for num in l { self.write_int(&num) }
+        for num in l { self.write_int(num) }
 
     }
 
@@ -76,86 +84,179 @@ impl Writer {
     }
 }
 
+osu_packet! {
+    /// An osu chat message, as sent by the client.
+    pub struct Message {
+        pub sender: String,
+        pub content: String,
+        pub target: String,
+        pub sender_id: i32,
+    }
+}
+
 // PACKET READING.
-pub struct Reader {
-    buf: Vec<u8>
+
+/// The ways reading a malformed packet can fail.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The buffer ended before the value did.
+    Truncated,
+    /// A string did not start with either `0x00` or `0x0b`.
+    BadStringMarker(u8),
+    /// A uleb128 did not fit in a u32.
+    UlebTooLong,
+    /// A string was longer than `MAX_STRING_LEN`.
+    StringTooLong(u32),
+    /// A string was not valid UTF-8.
+    InvalidUtf8,
+    /// The packet id is not one we know of.
+    UnknownPacket(u16),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "unexpected end of packet"),
+            Self::BadStringMarker(m) => write!(f, "invalid string marker {:#04x}", m),
+            Self::UlebTooLong => write!(f, "uleb128 does not fit in a u32"),
+            Self::StringTooLong(len) => write!(f, "string of {} bytes is too long", len),
+            Self::InvalidUtf8 => write!(f, "string is not valid UTF-8"),
+            Self::UnknownPacket(id) => write!(f, "unknown packet id {}", id),
+        }
+    }
 }
 
-impl Reader {
-    pub fn new(packet: Vec<u8>) -> Self {
-        Self { buf: packet }
+/// The longest string, in bytes, we accept from the client.
+pub const MAX_STRING_LEN: u32 = 1 << 20;
+
+/// # Reader
+/// A cursor over a borrowed packet buffer. Reading never copies or shifts the
+/// underlying bytes, only advancing the position.
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self { buf: packet, pos: 0 }
+    }
+
+    /// Returns the bytes that have not been read yet.
+    #[inline(always)]
+    fn remaining(&self) -> &'a [u8] {
+        &self.buf[self.pos..]
     }
 
-    /// Increments the reader buffer vector by `am`.
+    /// Increments the reader position by `am`, failing if there are not
+    /// enough bytes left.
     #[inline(always)]
-    pub fn incr_buffer(&mut self, am: usize) {
-        self.buf.drain(0..am);
+    pub fn incr_buffer(&mut self, am: usize) -> Result<(), ReadError> {
+        if am > self.buf.len() - self.pos {
+            return Err(ReadError::Truncated);
+        }
+        self.pos += am;
+        Ok(())
+    }
+
+    pub fn read_int<T: PacketVector>(&mut self) -> Result<T, ReadError> {
+        let (offs, int) = T::from_osu_vec(self.remaining())
+            .ok_or(ReadError::Truncated)?;
+        self.pos += offs;
+        Ok(int)
     }
 
-    pub fn read_int<T: PacketVector>(&mut self) -> T {
-        // So this is fine as these funcs specifically only use the first
-        // n bytes corresponding to them.
-        let (offs, int) = T::from_osu_vec(self.buf.clone()); //TODO: Do not use clone.
-        self.incr_buffer(offs);
-        int
+    /// Reads `len` raw bytes from the buffer.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        let bytes = self.remaining().get(..len).ok_or(ReadError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Reads all the bytes left in the buffer.
+    pub fn read_to_end(&mut self) -> &'a [u8] {
+        let bytes = self.remaining();
+        self.pos = self.buf.len();
+        bytes
     }
 
     /// Reads a list of i32s.
-    pub fn read_i32_l(&mut self) -> Vec<i32> {
+    pub fn read_i32_l(&mut self) -> Result<Vec<i32>, ReadError> {
         // First thing is len as u16.
-        let l_len: u16 = self.read_int();
+        let l_len: u16 = self.read_int()?;
 
         if l_len == 0 {
-            return Vec::new();
+            return Ok(Vec::new());
         }
         let mut l: Vec<i32> = Vec::with_capacity(l_len as usize);
 
         for _ in 0..l_len {
-            l.push(self.read_int());
+            l.push(self.read_int()?);
         }
-        l
+        Ok(l)
     }
 
     /// Reads the headers for an osu packet.
-    pub fn read_headers(&mut self) -> (u16, u32) {
-        let packet_id: u16 = self.read_int();
+    pub fn read_headers(&mut self) -> Result<(u16, u32), ReadError> {
+        let packet_id: u16 = self.read_int()?;
         // Peppy's padding byte
-        self.incr_buffer(1);
-        let packet_len: u32 = self.read_int();
+        self.incr_buffer(1)?;
+        let packet_len: u32 = self.read_int()?;
 
-        (packet_id, packet_len)
+        Ok((packet_id, packet_len))
     }
 
-    /// Reads an osu string (prefixed by uleb128 of its len)
-    pub fn read_string(&mut self) -> String {
-        // Uleb128 but we integrate it here as we can do some performance tricks.
-        let exists = self.buf[0] == 0x0b;
-        self.incr_buffer(1);
-        if !exists {return "".to_string();}
-
-        // Uleb reading
-        let (mut len, mut shift) = (0_u16, 0_u16);
-
-        loop {
-            let b = self.buf[0] as u16;
-            self.incr_buffer(1);
-
-            len |= (b & 0b01111111) << shift;
-            if b & 0b10000000 == 0 {
-                break;
+    /// Reads an unsigned 128 bit leb, the counterpart of
+    /// `Writer::write_uleb128`.
+    pub fn read_uleb128(&mut self) -> Result<u32, ReadError> {
+        let mut num: u32 = 0;
+
+        // A u32 takes at most 5 bytes, the last holding only the top 4 bits.
+        for shift in (0..32).step_by(7) {
+            let b = self.read_int::<u8>()?;
+            let part = (b & 0x7f) as u32;
+            if shift == 28 && part > 0x0f {
+                return Err(ReadError::UlebTooLong);
+            }
+
+            num |= part << shift;
+            if b & 0x80 == 0 {
+                return Ok(num);
             }
-            shift += 7;
         }
-        
-        let s = String::from_utf8(self.buf[0..len as usize].to_vec());
-        self.incr_buffer(len as usize);
-        s.unwrap_or(String::new())
+
+        Err(ReadError::UlebTooLong)
+    }
+
+    /// Reads an osu string (prefixed by uleb128 of its len)
+    pub fn read_string(&mut self) -> Result<String, ReadError> {
+        let marker: u8 = self.read_int()?;
+        match marker {
+            0x00 => return Ok(String::new()),
+            0x0b => {},
+            m => return Err(ReadError::BadStringMarker(m)),
+        }
+
+        let len = self.read_uleb128()?;
+        if len > MAX_STRING_LEN {
+            return Err(ReadError::StringTooLong(len));
+        }
+
+        let bytes = self.read_bytes(len as usize)?;
+        std::str::from_utf8(bytes)
+            .map(str::to_owned)
+            .map_err(|_| ReadError::InvalidUtf8)
+    }
+
+    /// Reads an osu chat message.
+    pub fn read_message(&mut self) -> Result<Message, ReadError> {
+        Message::decode(self)
     }
 
     #[inline]
     /// Checks if the reader buffer is empty.
     pub fn empty(&self) -> bool {
-        self.buf.is_empty()
+        self.pos >= self.buf.len()
     }
 }
 
@@ -179,13 +280,17 @@ impl SimplePacketQueue {
         self.bytes.append(&mut b);
     }
 
-    /// # As Vec
+    /// # Into Bytes
     /// Returns the pure queue bytes, destroying the queue in the process.
-    pub fn as_bytes(self) -> Vec<u8> {
+    pub fn into_bytes(self) -> Vec<u8> {
         self.bytes
     }
 }
 
+impl Default for SimplePacketQueue {
+    fn default() -> Self { Self::new() }
+}
+
 impl From<Vec<u8>> for SimplePacketQueue {
     fn from(b: Vec<u8>) -> Self {
         Self {bytes: b}
@@ -193,75 +298,251 @@ impl From<Vec<u8>> for SimplePacketQueue {
 }
 
 // Ok so you see, this is a mess, but it lets us use generics elsewhere.
-pub trait PacketVector {
+pub trait PacketVector: Sized {
     fn to_osu_vec(&self) -> Vec<u8>;
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self);
+    /// Reads the value from the start of `bytes`, returning the amount of
+    /// bytes used alongside it, or `None` if there are not enough bytes.
+    fn from_osu_vec(bytes: &[u8]) -> Option<(usize, Self)>;
 }
 
-// Heck.
-impl PacketVector for u8 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (1, bytes[0])
-    }
+// Heck. All numbers are written as little endian bytes.
+macro_rules! packet_vector_num {
+    ($($num: ty),*) => {$(
+        impl PacketVector for $num {
+            fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
+            fn from_osu_vec(bytes: &[u8]) -> Option<(usize, Self)> {
+                const SIZE: usize = std::mem::size_of::<$num>();
+                let arr: [u8; SIZE] = bytes.get(..SIZE)?.try_into().ok()?;
+                Some((SIZE, Self::from_le_bytes(arr)))
+            }
+        }
+    )*};
 }
 
-impl PacketVector for i8 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (1, bytes[0] as i8)
+packet_vector_num!(u8, i8, u16, i16, u32, i32, u64, i64, f32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a single value into a packet and reads it back from the body.
+    fn round_trip_int<T: PacketVector + PartialEq + std::fmt::Debug>(num: T) {
+        let mut w = Writer::new(0);
+        w.write_int(&num);
+        let packet = w.build();
+
+        let mut r = Reader::new(&packet);
+        let (_, len) = r.read_headers().unwrap();
+        assert_eq!(len as usize, std::mem::size_of::<T>());
+        assert_eq!(r.read_int::<T>().unwrap(), num);
+        assert!(r.empty());
     }
-}
 
-impl PacketVector for u16 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (2, Self::from_le_bytes([bytes[0], bytes[1]]))
+    macro_rules! round_trip_ints {
+        ($($num: ty),*) => {$(
+            round_trip_int::<$num>(0 as $num);
+            round_trip_int::<$num>(<$num>::MIN);
+            round_trip_int::<$num>(<$num>::MAX);
+        )*};
     }
-}
 
-impl PacketVector for i16 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (2, Self::from_le_bytes([bytes[0], bytes[1]]))
+    /// Writes a string and returns its encoded bytes (the packet body).
+    fn encode_string(s: &String) -> Vec<u8> {
+        let mut w = Writer::new(0);
+        w.write_string(s);
+        w.build()[7..].to_vec()
     }
-}
 
-impl PacketVector for u32 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (4, Self::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    fn round_trip_string(s: &str) {
+        let s = s.to_string();
+        let body = encode_string(&s);
+
+        let mut r = Reader::new(&body);
+        assert_eq!(r.read_string().unwrap(), s);
+        assert!(r.empty());
     }
-}
 
-impl PacketVector for i32 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (4, Self::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    #[test]
+    fn ints_round_trip() {
+        round_trip_ints!(u8, i8, u16, i16, u32, i32, u64, i64);
+        round_trip_int(0.0_f32);
+        round_trip_int(-1.5_f32);
+        round_trip_int(f32::MIN);
+        round_trip_int(f32::MAX);
     }
-}
 
-impl PacketVector for u64 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (8, Self::from_le_bytes([
-            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]
-        ]))
+    #[test]
+    fn ints_are_little_endian() {
+        let mut w = Writer::new(0);
+        w.write_int(&0x0102_u16);
+        w.write_int(&-2_i32);
+        w.write_int(&1.0_f32);
+        assert_eq!(w.build()[7..], [0x02, 0x01, 0xfe, 0xff, 0xff, 0xff, 0, 0, 0x80, 0x3f]);
     }
-}
 
-impl PacketVector for i64 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (8, Self::from_le_bytes([
-            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]
-        ]))
+    #[test]
+    fn uleb128_boundaries() {
+        let cases: [(usize, &[u8]); 4] = [
+            (127, &[0x7f]),
+            (128, &[0x80, 0x01]),
+            (16383, &[0xff, 0x7f]),
+            (16384, &[0x80, 0x80, 0x01]),
+        ];
+
+        for (len, uleb) in cases.iter() {
+            let s = "a".repeat(*len);
+            let body = encode_string(&s);
+            assert_eq!(body[0], 0x0b);
+            assert_eq!(&body[1..=uleb.len()], *uleb, "length {}", len);
+            assert_eq!(body.len(), 1 + uleb.len() + len);
+
+            round_trip_string(&s);
+        }
+    }
+
+    #[test]
+    fn uleb128_round_trips() {
+        let nums = [0, 1, 127, 128, 16383, 16384, 65535, 65536, 2097151, 2097152, u32::MAX];
+
+        for &num in nums.iter() {
+            let mut w = Writer::new(0);
+            w.write_uleb128(num);
+            let bytes = w.build()[7..].to_vec();
+
+            let mut r = Reader::new(&bytes);
+            assert_eq!(r.read_uleb128().unwrap(), num);
+            assert!(r.empty());
+        }
+    }
+
+    #[test]
+    fn bad_uleb128s_fail() {
+        // Too many bytes.
+        let mut r = Reader::new(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01]);
+        assert!(matches!(r.read_uleb128(), Err(ReadError::UlebTooLong)));
+        // Overflowing the top 4 bits of a u32.
+        let mut r = Reader::new(&[0xff, 0xff, 0xff, 0xff, 0x1f]);
+        assert!(matches!(r.read_uleb128(), Err(ReadError::UlebTooLong)));
+        // Ending before the last byte.
+        let mut r = Reader::new(&[0x80, 0x80]);
+        assert!(matches!(r.read_uleb128(), Err(ReadError::Truncated)));
+    }
+
+    #[test]
+    fn long_strings() {
+        round_trip_string(&"a".repeat(65535));
+        round_trip_string(&"a".repeat(65536));
+        round_trip_string(&"a".repeat(MAX_STRING_LEN as usize));
+
+        // Lengths above the limit fail before reading any further.
+        let mut w = Writer::new(0);
+        w.write_uleb128(MAX_STRING_LEN + 1);
+        let body = [&[0x0b][..], &w.build()[7..]].concat();
+        let mut r = Reader::new(&body);
+        assert!(matches!(r.read_string(), Err(ReadError::StringTooLong(_))));
+    }
+
+    #[test]
+    fn strings_round_trip() {
+        assert_eq!(encode_string(&String::new()), [0]);
+        round_trip_string("");
+        round_trip_string("hello");
+        round_trip_string("héllo wörld");
+        round_trip_string("日本語のチャット");
+        round_trip_string("🎵🥁");
+        // The length is in bytes, not characters.
+        assert_eq!(encode_string(&"é".to_string()), [0x0b, 2, 0xc3, 0xa9]);
+    }
+
+    #[test]
+    fn bad_strings_fail() {
+        let mut r = Reader::new(&[0x0c, 1, b'a']);
+        assert!(matches!(r.read_string(), Err(ReadError::BadStringMarker(0x0c))));
+
+        let mut r = Reader::new(&[0x0b, 5, b'a']);
+        assert!(matches!(r.read_string(), Err(ReadError::Truncated)));
+
+        let mut r = Reader::new(&[0x0b, 2, 0xff, 0xfe]);
+        assert!(matches!(r.read_string(), Err(ReadError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn i32_lists_round_trip() {
+        let lists: [&[i32]; 3] = [&[], &[1], &[i32::MIN, -1, 0, 1, i32::MAX]];
+
+        for list in lists.iter() {
+            let mut w = Writer::new(0);
+            w.write_i32_list(list);
+            let packet = w.build();
+            assert_eq!(packet.len(), 7 + 2 + list.len() * 4);
+
+            let mut r = Reader::new(&packet[7..]);
+            assert_eq!(r.read_i32_l().unwrap(), *list);
+            assert!(r.empty());
+        }
+    }
+
+    #[test]
+    fn truncated_reads_fail() {
+        let mut r = Reader::new(&[1, 2, 3]);
+        assert!(matches!(r.read_int::<i32>(), Err(ReadError::Truncated)));
+        // A failed read does not consume anything.
+        assert_eq!(r.read_int::<u16>().unwrap(), 0x0201);
+        assert!(matches!(r.read_bytes(2), Err(ReadError::Truncated)));
+        assert_eq!(r.read_to_end(), [3]);
+        assert!(r.empty());
+
+        let mut r = Reader::new(&[2, 0, 1, 0, 0, 0]);
+        assert!(matches!(r.read_i32_l(), Err(ReadError::Truncated)));
+    }
+
+    #[test]
+    fn multi_packet_bodies() {
+        let mut first = Writer::new(4);
+        let mut second = Writer::new(24);
+        second.write_string(&"hi".to_string());
+        let mut third = Writer::new(85);
+        third.write_i32_list(&[3, 4]);
+        third.write_int(&7_u8);
+
+        let mut q = SimplePacketQueue::new();
+        q.queue(first.build());
+        q.queue(second.build());
+        q.queue(third.build());
+        let stream = q.into_bytes();
+
+        let mut r = Reader::new(&stream);
+        let mut packets = Vec::new();
+        while !r.empty() {
+            let (id, len) = r.read_headers().unwrap();
+            packets.push((id, r.read_bytes(len as usize).unwrap()));
+        }
+
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[0], (4, &[][..]));
+        assert_eq!(packets[1], (24, &[0x0b, 2, b'h', b'i'][..]));
+        assert_eq!(packets[2].0, 85);
+
+        let mut body = Reader::new(packets[2].1);
+        assert_eq!(body.read_i32_l().unwrap(), [3, 4]);
+        assert_eq!(body.read_int::<u8>().unwrap(), 7);
+        assert!(body.empty());
     }
-}
 
-impl PacketVector for f32 {
-    fn to_osu_vec(&self) -> Vec<u8> { self.to_le_bytes().to_vec() }
-    fn from_osu_vec(bytes: Vec<u8>) -> (usize, Self) {
-        (4, Self::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    #[test]
+    fn messages_round_trip() {
+        let msg = Message {
+            sender: "bob".to_string(),
+            content: "héllo".to_string(),
+            target: "#osu".to_string(),
+            sender_id: 3,
+        };
+        let mut w = Writer::new(0);
+        msg.encode(&mut w);
+        let packet = w.build();
+
+        let mut r = Reader::new(&packet[7..]);
+        assert_eq!(r.read_message().unwrap(), msg);
+        assert!(r.empty());
     }
 }
@@ -0,0 +1,157 @@
+// Packets sent by the server.
+use crate::packets::packet::{osu_packet, packet_enum, OsuEncode, Raw, MatchData};
+use crate::packets::rw::{Writer, Reader, ReadError, Message};
+
+packet_enum! {
+    /// # Server Packet
+    /// A packet sent by the server, alongside its payload.
+    pub enum ServerPacket {
+        SRV_USER_ID => UserId(i32),
+        SRV_SEND_MESSAGE => SendMessage(Message),
+        SRV_PONG => Pong,
+        SRV_USER_STATS => UserStats(UserStats),
+        SRV_USER_LOGOUT => UserLogout(UserLogout),
+        SRV_SPECTATOR_JOINED => SpectatorJoined(i32),
+        SRV_SPECTATOR_LEFT => SpectatorLeft(i32),
+        SRV_SPECTATE_FRAMES => SpectateFrames(Raw),
+        SRV_VERSION_UPDATE => VersionUpdate,
+        SRV_SPECTATOR_CANT_SPECTATE => SpectatorCantSpectate(i32),
+        SRV_GET_ATTENTION => GetAttention,
+        SRV_NOTIFICATION => Notification(String),
+        SRV_UPDATE_MATCH => UpdateMatch(MatchData),
+        SRV_NEW_MATCH => NewMatch(MatchData),
+        SRV_DISPOSE_MATCH => DisposeMatch(i32),
+        SRV_TOGGLE_BLOCK_NON_FRIEND_DMS => ToggleBlockNonFriendDms,
+        SRV_MATCH_JOIN_SUCCESS => MatchJoinSuccess(MatchData),
+        SRV_MATCH_JOIN_FAIL => MatchJoinFail,
+        SRV_FELLOW_SPECTATOR_JOINED => FellowSpectatorJoined(i32),
+        SRV_FELLOW_SPECTATOR_LEFT => FellowSpectatorLeft(i32),
+        SRV_ALL_PLAYERS_LOADED => AllPlayersLoaded,
+        SRV_MATCH_START => MatchStart(MatchData),
+        SRV_MATCH_SCORE_UPDATE => MatchScoreUpdate(Raw),
+        SRV_MATCH_TRANSFER_HOST => MatchTransferHost,
+        SRV_MATCH_ALL_PLAYERS_LOADED => MatchAllPlayersLoaded,
+        SRV_MATCH_PLAYER_FAILED => MatchPlayerFailed(i32),
+        SRV_MATCH_COMPLETE => MatchComplete,
+        SRV_MATCH_SKIP => MatchSkip,
+        SRV_CHANNEL_JOIN_SUCCESS => ChannelJoinSuccess(String),
+        SRV_CHANNEL_INFO => ChannelInfo(ChannelInfo),
+        SRV_CHANNEL_KICK => ChannelKick(String),
+        SRV_CHANNEL_AUTO_JOIN => ChannelAutoJoin(ChannelInfo),
+        SRV_BEATMAP_INFO_REPLY => BeatmapInfoReply(BeatmapInfoReply),
+        SRV_PRIVILEGES => Privileges(i32),
+        SRV_FRIENDS_LIST => FriendsList(Vec<i32>),
+        SRV_PROTOCOL_VERSION => ProtocolVersion(i32),
+        SRV_MAIN_MENU_ICON => MainMenuIcon(String),
+        SRV_MATCH_PLAYER_SKIPPED => MatchPlayerSkipped(i32),
+        SRV_USER_PRESENCE => UserPresence(UserPresence),
+        SRV_RESTART => Restart(i32),
+        SRV_MATCH_INVITE => MatchInvite(Message),
+        SRV_CHANNEL_INFO_END => ChannelInfoEnd,
+        SRV_MATCH_CHANGE_PASSWORD => MatchChangePassword(String),
+        SRV_SILENCE_END => SilenceEnd(i32),
+        SRV_USER_SILENCED => UserSilenced(i32),
+        SRV_USER_PRESENCE_SINGLE => UserPresenceSingle(i32),
+        SRV_USER_PRESENCE_BUNDLE => UserPresenceBundle(Vec<i32>),
+        SRV_USER_DM_BLOCKED => UserDmBlocked(Message),
+        SRV_TARGET_IS_SILENCED => TargetIsSilenced(Message),
+        SRV_VERSION_UPDATE_FORCED => VersionUpdateForced,
+        SRV_SWITCH_SERVER => SwitchServer(i32),
+        SRV_ACCOUNT_RESTRICTED => AccountRestricted,
+        SRV_MATCH_ABORT => MatchAbort,
+        SRV_SWITCH_TOURNAMENT_SERVER => SwitchTournamentServer(String),
+    }
+}
+
+osu_packet! {
+    /// The stats and current action of a player.
+    pub struct UserStats {
+        pub user_id: i32,
+        pub action: u8,
+        pub text: String,
+        pub map_md5: String,
+        pub mods: u32,
+        pub mode: u8,
+        pub map_id: i32,
+        pub ranked_score: i64,
+        /// As a fraction rather than a percentage.
+        pub accuracy: f32,
+        pub playcount: i32,
+        pub total_score: i64,
+        pub rank: i32,
+        pub pp: i16,
+    }
+}
+
+osu_packet! {
+    /// A player logging out.
+    pub struct UserLogout {
+        pub user_id: i32,
+        pub reason: u8,
+    }
+}
+
+osu_packet! {
+    /// The presence of a player (their name, location and rank).
+    pub struct UserPresence {
+        pub user_id: i32,
+        pub name: String,
+        /// The UTC offset in hours, plus 24.
+        pub utc_offset: u8,
+        pub country: u8,
+        /// `BanchoPriv` flags, with the mode in the upper 3 bits.
+        pub bancho_priv: u8,
+        pub longitude: f32,
+        pub latitude: f32,
+        pub rank: i32,
+    }
+}
+
+osu_packet! {
+    /// A chat channel, as listed to the client.
+    pub struct ChannelInfo {
+        pub name: String,
+        pub topic: String,
+        pub player_count: i16,
+    }
+}
+
+osu_packet! {
+    /// The info of a single beatmap, as requested by the client.
+    pub struct BeatmapInfo {
+        /// The position of the map in the request.
+        pub index: i16,
+        pub map_id: i32,
+        pub set_id: i32,
+        pub thread_id: i32,
+        pub status: u8,
+        /// The player's best grade on the map in each mode.
+        pub osu_grade: u8,
+        pub catch_grade: u8,
+        pub taiko_grade: u8,
+        pub mania_grade: u8,
+        pub map_md5: String,
+    }
+}
+
+/// The reply to a `BeatmapInfoRequest`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatmapInfoReply {
+    pub maps: Vec<BeatmapInfo>,
+}
+
+// The list is prefixed by an i32 length, unlike i32 lists.
+impl OsuEncode for BeatmapInfoReply {
+    fn encode(&self, w: &mut Writer) {
+        w.write_int(&(self.maps.len() as i32));
+        for map in self.maps.iter() { map.encode(w); }
+    }
+
+    fn decode(r: &mut Reader<'_>) -> Result<Self, ReadError> {
+        let maps = (0..r.read_int::<i32>()?)
+            .map(|_| BeatmapInfo::decode(r))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { maps })
+    }
+}
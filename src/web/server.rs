@@ -1,9 +1,10 @@
 // The cu.rs web server wrapper.
-use ntex::http::{Request, Response, Payload, HttpService, Method, HeaderMap};
+use ntex::http::{Request, Response, Payload, HttpService};
 use ntex::server::Server;
 use futures::StreamExt;
 use crate::logger;
-use crate::packets::router::handle_bancho;
+use crate::packets::router::BanchoServer;
+use std::sync::Arc;
 
 /// The cu.rs request object wrapper, offering common functions.
 pub struct RequestContext {
@@ -13,7 +14,7 @@ pub struct RequestContext {
 impl RequestContext {
     pub fn from_req(req: Request) -> Self {
         Self {
-            req: req
+            req
         }
     }
 
@@ -43,13 +44,10 @@ impl RequestContext {
         let mut body: Vec<u8> = Vec::new();
         let payload: &mut Payload = self.req.payload();
 
-        loop {
-            if let Some(b) = payload.next().await {
-                if let Ok(byte) = b {
-                    body.extend_from_slice(&byte);
-                }
+        while let Some(b) = payload.next().await {
+            if let Ok(byte) = b {
+                body.extend_from_slice(&byte);
             }
-            else {break;}
         }
 
         body
@@ -61,10 +59,11 @@ impl RequestContext {
     #[inline(always)]
     pub async fn read_string(&mut self) -> String {
         let body = self.read_body().await;
-        String::from_utf8(body).unwrap_or(String::new())
+        String::from_utf8(body).unwrap_or_default()
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 pub enum Address {
     UNIXSock(String),
     IPAddress(String),
@@ -74,21 +73,22 @@ pub enum Address {
 
 /// # Server Start
 /// Starts listening on an address.
-async fn start_server_ip(addr: String) -> std::io::Result<()> {
+async fn start_server_ip(addr: String, server: Arc<BanchoServer>) -> std::io::Result<()> {
     logger::info(format!("Starting cu.rs on http://{}", addr));
 
     Server::build()
-    .bind("cu.rs", addr, || {
-        HttpService::build().finish(handle_conn).tcp()
+    .bind("cu.rs", addr, move || {
+        let server = server.clone();
+        HttpService::build().finish(move |req| handle_conn(req, server.clone())).tcp()
     })?
     .run()
     .await
 }
 
-pub async fn start_server(addr: Address) -> std::io::Result<()> {
+pub async fn start_server(addr: Address, server: Arc<BanchoServer>) -> std::io::Result<()> {
     match addr {
         Address::IPAddress(address) => {
-            start_server_ip(address).await
+            start_server_ip(address, server).await
         },
         Address::UNIXSock(_) => {
             panic!("Unix sockets are not yet implemented!");
@@ -96,8 +96,8 @@ pub async fn start_server(addr: Address) -> std::io::Result<()> {
     }
 }
 
-async fn handle_conn(req: Request) -> Result<Response, std::io::Error> {
+async fn handle_conn(req: Request, server: Arc<BanchoServer>) -> Result<Response, std::io::Error> {
     let req_ctx = RequestContext::from_req(req);
 
-    Ok(handle_bancho(req_ctx).await)
+    Ok(server.handle_conn(req_ctx).await)
 }